@@ -0,0 +1,57 @@
+//!
+//! This example showcases the `actix` feature: the login and callback routes of every strategy, and a route only
+//! available to the signed in users.
+//!
+//! Run it with `cargo run --example actix-scope --features actix`.
+//!
+
+use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+use actix_web::{cookie::Key, web, App, HttpServer, Responder};
+use passport_strategies::{
+    integrations::{
+        actix::{passport_scope, RequireAuth, SharedPassport},
+        AuthUser,
+    },
+    passport::{Choice, Passport, Redirect},
+    strategies::GithubStrategy,
+};
+use tokio::sync::RwLock;
+
+async fn me(user: AuthUser) -> impl Responder {
+    web::Json(user)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let passport = Passport::default()
+        .redirect_urls(Redirect::new("http://localhost:8000/", "http://localhost:8000/me").unwrap())
+        .strategize(
+            Choice::Github,
+            GithubStrategy::new(
+                "<client_id>",
+                "<client_secret>",
+                &["user:email"],
+                "http://localhost:8000/auth/github/callback",
+            ),
+        )
+        .unwrap();
+
+    let passport = SharedPassport::new(RwLock::new(passport));
+    let key = Key::generate();
+    HttpServer::new(move || {
+        App::new()
+            .wrap(SessionMiddleware::new(
+                CookieSessionStore::default(),
+                key.clone(),
+            ))
+            .service(passport_scope("/auth", passport.clone()))
+            .service(
+                web::resource("/me")
+                    .wrap(RequireAuth::new("/auth/github"))
+                    .to(me),
+            )
+    })
+    .bind(("0.0.0.0", 8000))?
+    .run()
+    .await
+}
@@ -7,21 +7,21 @@
 use actix_web::{
     http,
     middleware::Logger,
-    web::{self, Data, Query},
+    web::{self, Data, Form, Query},
     App, HttpResponse, HttpServer,
 };
 
 use passport_strategies::{
-    passport::{Choice, Passport, Redirect, StateCode},
-    strategies::{DiscordStrategy, GoogleStrategy, RedditStrategy},
+    passport::{Choice, Passport, Redirect, ResponseMode, StateCode},
+    strategies::{DiscordStrategy, GoogleStrategy, MicrosoftStrategy, RedditStrategy},
 };
 
 use tokio::sync::RwLock;
 
 pub async fn discord(passport: Data<RwLock<Passport>>) -> HttpResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
 
-    let url = auth.redirect_url(Choice::Discord);
+    let url = auth.redirect_url(Choice::Discord, None).unwrap();
 
     HttpResponse::SeeOther()
         .append_header((http::header::LOCATION, url))
@@ -29,9 +29,9 @@ pub async fn discord(passport: Data<RwLock<Passport>>) -> HttpResponse {
 }
 
 pub async fn reddit(passport: Data<RwLock<Passport>>) -> HttpResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
 
-    let url = auth.redirect_url(Choice::Reddit);
+    let url = auth.redirect_url(Choice::Reddit, None).unwrap();
 
     HttpResponse::SeeOther()
         .append_header((http::header::LOCATION, url))
@@ -39,24 +39,33 @@ pub async fn reddit(passport: Data<RwLock<Passport>>) -> HttpResponse {
 }
 
 pub async fn google(passport: Data<RwLock<Passport>>) -> HttpResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
 
-    let url = auth.redirect_url(Choice::Google);
+    let url = auth.redirect_url(Choice::Google, None).unwrap();
 
     HttpResponse::SeeOther()
         .append_header((http::header::LOCATION, url))
         .finish()
 }
 
-/// With reddit, you still have to go an extra mile to get the user profile.
-/// Passport only retrieves the access_token and refresh token for RedditStrategy unlike
-/// the other strategies
-/// The user profile can be accessed at (https://oauth.reddit.com/api/v1/me)
+pub async fn microsoft(passport: Data<RwLock<Passport>>) -> HttpResponse {
+    let auth = passport.read().await;
+
+    let url = auth.redirect_url(Choice::Microsoft, None).unwrap();
+
+    HttpResponse::SeeOther()
+        .append_header((http::header::LOCATION, url))
+        .finish()
+}
+
+/// Reddit requires the client credentials as HTTP Basic auth and a custom `User-Agent` on the token endpoint,
+/// which the RedditStrategy takes care of. No profile is requested: the profile of the response is the token
+/// response itself.
 pub async fn authenticate_reddit(
     Query(statecode): Query<StateCode>,
     passport: Data<RwLock<Passport>>,
 ) -> HttpResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
     let (_response, url) = auth.authenticate(Choice::Reddit, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
@@ -73,7 +82,7 @@ pub async fn authenticate_google(
     Query(statecode): Query<StateCode>,
     passport: Data<RwLock<Passport>>,
 ) -> HttpResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
     let (_response, url) = auth.authenticate(Choice::Google, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
@@ -90,10 +99,8 @@ pub async fn authenticate_discord(
     Query(statecode): Query<StateCode>,
     passport: Data<RwLock<Passport>>,
 ) -> HttpResponse {
-    let mut auth = passport.write().await;
-    let (_response, url) = auth
-        .authenticate(Choice::Discord, statecode)
-        .await;
+    let auth = passport.read().await;
+    let (_response, url) = auth.authenticate(Choice::Discord, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
     // and an `Oauth2ServerResponse` which contains the access_token, refresh_token and user profile.
@@ -105,6 +112,23 @@ pub async fn authenticate_discord(
         .finish()
 }
 
+/// The Microsoft strategy below is configured with `ResponseMode::FormPost`, so the `code` and `state`
+/// are sent as a form `POST` and the `StateCode` is extracted from the form body instead of the query.
+pub async fn authenticate_msft(
+    Form(statecode): Form<StateCode>,
+    passport: Data<RwLock<Passport>>,
+) -> HttpResponse {
+    let auth = passport.read().await;
+    let (_response, url) = auth.authenticate(Choice::Microsoft, statecode).await;
+
+    // You will receive the redirect url which is determined based on authentication status `failed` or `success`
+    // and an `Oauth2ServerResponse` which contains the access_token, refresh_token and user profile.
+
+    HttpResponse::SeeOther()
+        .append_header((http::header::LOCATION, url))
+        .finish()
+}
+
 pub async fn signup_get() -> HttpResponse {
     let html = r#"<!DOCTYPE html>
        <html lang="en">
@@ -116,6 +140,7 @@ pub async fn signup_get() -> HttpResponse {
            <a href="/discord">discord</a>
            <a href="/google">google</a>
            <a href="/reddit">reddit</a>
+           <a href="/microsoft">microsoft</a>
        </body>
        </html>
        "#;
@@ -174,6 +199,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &["identity"],
                 "<redirect_url>",
             ),
+        )?
+        .strategize(
+            Choice::Microsoft,
+            MicrosoftStrategy::new(
+                "<client_id>",
+                "<client_secret>",
+                &["user.read"],
+                "<redirect_url>",
+            )
+            .set_response_mode(ResponseMode::FormPost),
         )?;
 
     let passport_clone = Data::new(RwLock::new(passport));
@@ -194,6 +229,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .route("/discord", web::get().to(discord))
             .route("/success", web::get().to(success))
             .route("/reddit", web::get().to(reddit))
+            .route("/microsoft", web::get().to(microsoft))
+            .route(
+                "/<redirect_url_endpoint_for_microsoft>",
+                web::post().to(authenticate_msft),
+            )
             .route(
                 "<redirect_url_endpoint_for_reddit>",
                 web::get().to(authenticate_reddit),
@@ -0,0 +1,45 @@
+//!
+//! This example showcases the `axum` feature: the login and callback routes of every strategy, and a route only
+//! available to the signed in users.
+//!
+//! Run it with `cargo run --example axum-router --features axum`.
+//!
+
+use axum::{response::IntoResponse, routing::get, Json, Router};
+use passport_strategies::{
+    integrations::{
+        axum::{passport_router, RequireAuth, SharedPassport},
+        AuthUser,
+    },
+    passport::{Choice, Passport, Redirect},
+    strategies::GithubStrategy,
+};
+
+async fn me(user: AuthUser) -> impl IntoResponse {
+    Json(user)
+}
+
+#[tokio::main]
+async fn main() {
+    let passport = Passport::default()
+        .redirect_urls(Redirect::new("http://localhost:8000/", "http://localhost:8000/me").unwrap())
+        .strategize(
+            Choice::Github,
+            GithubStrategy::new(
+                "<client_id>",
+                "<client_secret>",
+                &["user:email"],
+                "http://localhost:8000/auth/github/callback",
+            ),
+        )
+        .unwrap();
+
+    let passport = SharedPassport::from(passport);
+    let app = Router::new()
+        .route("/me", get(me))
+        .route_layer(RequireAuth::new(&passport, "/auth/github"))
+        .merge(passport_router(passport));
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
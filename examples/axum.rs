@@ -7,15 +7,16 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::Query,
+    extract::{Form, Query},
     response::{Html, IntoResponse, Redirect},
-    routing::get,
+    routing::{get, post},
     Extension, Router,
 };
 use passport_strategies::{
     passport::{Choice, Passport},
     strategies::{
-        DiscordStrategy, GithubStrategy, GoogleStrategy, MicrosoftStrategy, RedditStrategy,
+        AppleStrategy, DiscordStrategy, GithubStrategy, GoogleStrategy, MicrosoftStrategy,
+        RedditStrategy,
     },
 };
 
@@ -23,49 +24,55 @@ use tokio::sync::RwLock;
 use tracing::info;
 
 pub async fn discord(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let url = auth.redirect_url(Choice::Discord);
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Discord, None).unwrap();
 
     Redirect::temporary(&url)
 }
 
 pub async fn reddit(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let url = auth.redirect_url(Choice::Reddit);
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Reddit, None).unwrap();
 
     Redirect::temporary(&url)
 }
 
 pub async fn google(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let url = auth.redirect_url(Choice::Google);
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Google, None).unwrap();
 
     Redirect::temporary(&url)
 }
 
 pub async fn microsoft(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let url = auth.redirect_url(Choice::Microsoft);
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Microsoft, None).unwrap();
 
     Redirect::temporary(&url)
 }
 
 pub async fn github(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let url = auth.redirect_url(Choice::Github);
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Github, None).unwrap();
 
     Redirect::temporary(&url)
 }
 
-/// With reddit, you still have to go an extra mile to get the user profile.
-/// Passport only retrieves the access_token and refresh token for RedditStrategy unlike
-/// the other strategies
-/// The user profile can be accessed at (https://oauth.reddit.com/api/v1/me)
+pub async fn apple(Extension(passport): Extension<Arc<RwLock<Passport>>>) -> impl IntoResponse {
+    let auth = passport.read().await;
+    let url = auth.redirect_url(Choice::Apple, None).unwrap();
+
+    Redirect::temporary(&url)
+}
+
+/// Reddit requires the client credentials as HTTP Basic auth and a custom `User-Agent` on the token endpoint,
+/// which the RedditStrategy takes care of. No profile is requested: the profile of the response is the token
+/// response itself.
 pub async fn authenticate_reddit(
     Extension(passport): Extension<Arc<RwLock<Passport>>>,
     Query(statecode): Query<passport_strategies::passport::StateCode>,
 ) -> impl IntoResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
     let (_response, url) = auth.authenticate(Choice::Reddit, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
@@ -80,7 +87,7 @@ pub async fn authenticate_google(
     Extension(passport): Extension<Arc<RwLock<Passport>>>,
     Query(statecode): Query<passport_strategies::passport::StateCode>,
 ) -> impl IntoResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
     let (_response, url) = auth.authenticate(Choice::Google, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
@@ -95,7 +102,7 @@ pub async fn authenticate_github(
     Extension(passport): Extension<Arc<RwLock<Passport>>>,
     Query(statecode): Query<passport_strategies::passport::StateCode>,
 ) -> impl IntoResponse {
-    let mut auth = passport.write().await;
+    let auth = passport.read().await;
     let (_response, url) = auth.authenticate(Choice::Github, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
@@ -110,10 +117,8 @@ pub async fn authenticate_msft(
     Extension(passport): Extension<Arc<RwLock<Passport>>>,
     Query(statecode): Query<passport_strategies::passport::StateCode>,
 ) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let (_response, url) = auth
-        .authenticate(Choice::Microsoft, statecode)
-        .await;
+    let auth = passport.read().await;
+    let (_response, url) = auth.authenticate(Choice::Microsoft, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
     // and an `Oauth2ServerResponse` which contains the access_token, refresh_token and user profile.
@@ -127,10 +132,8 @@ pub async fn authenticate_discord(
     Extension(passport): Extension<Arc<RwLock<Passport>>>,
     Query(statecode): Query<passport_strategies::passport::StateCode>,
 ) -> impl IntoResponse {
-    let mut auth = passport.write().await;
-    let (_response, url) = auth
-        .authenticate(Choice::Discord, statecode)
-        .await;
+    let auth = passport.read().await;
+    let (_response, url) = auth.authenticate(Choice::Discord, statecode).await;
 
     // You will receive the redirect url which is determined based on authentication status `failed` or `success`
     // and an `Oauth2ServerResponse` which contains the access_token, refresh_token and user profile.
@@ -140,6 +143,22 @@ pub async fn authenticate_discord(
     Redirect::temporary(&url)
 }
 
+/// Apple sends the `code` and `state` as a form `POST` (`response_mode=form_post`),
+/// so the `StateCode` is extracted from the form body instead of the query.
+pub async fn authenticate_apple(
+    Extension(passport): Extension<Arc<RwLock<Passport>>>,
+    Form(statecode): Form<passport_strategies::passport::StateCode>,
+) -> impl IntoResponse {
+    let auth = passport.read().await;
+    let (_response, url) = auth.authenticate(Choice::Apple, statecode).await;
+
+    // You will receive the redirect url which is determined based on authentication status `failed` or `success`
+    // and an `Oauth2ServerResponse` which contains the access_token, refresh_token and user profile.
+    // Apple only sends the user's name on the first authorization, make sure to save it.
+
+    Redirect::to(&url)
+}
+
 pub async fn signup_get() -> impl IntoResponse {
     let html = r#"
     <!DOCTYPE html>
@@ -155,6 +174,7 @@ pub async fn signup_get() -> impl IntoResponse {
         <a href="/reddit">reddit</a>
         <a href="/microsoft">microsoft</a>
         <a href="/github">github</a>
+        <a href="/apple">apple</a>
     </body>
     </html>
     "#;
@@ -231,6 +251,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 &["profile"],
                 "<redirect_url>",
             ),
+        )?
+        .strategize(
+            Choice::Apple,
+            AppleStrategy::new(
+                "<services_id>",
+                "<team_id>",
+                "<key_id>",
+                "<contents_of_the_p8_private_key>",
+                &["name", "email"],
+                "<redirect_url>",
+            )?,
         )?;
 
     let passport_clone = Arc::new(RwLock::new(passport));
@@ -258,6 +289,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/success", get(success))
         .route("/microsoft", get(microsoft))
         .route("/reddit", get(reddit))
+        .route("/apple", get(apple))
+        .route(
+            "/<redirect_url_endpoint_for_apple>",
+            post(authenticate_apple),
+        )
         .route(
             "/<redirect_url_endpoint_for_reddit>",
             get(authenticate_reddit),
@@ -16,4 +16,92 @@ pub enum Error {
     MissingAuthorizationCodeAndCsrfToken,
     #[error("Parse Error: {0}")]
     ParseError(#[from] ParseError),
+    #[error("Jwt Error: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("Json Error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A key could not be used for signing, e.g. the HMAC key of the OAuth 1.0a signature.
+    #[error("Key is invalid: {0}")]
+    InvalidKey(String),
+    /// The provider did not return an `id_token` where one was expected.
+    #[error("Id Token is missing")]
+    MissingIdToken,
+    /// The provider responded, or posted to the redirect url, data that could not be deserialized.
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    #[error("Access Token is missing")]
+    MissingAccessToken,
+    /// The profile was not found within the envelope of the profile response.
+    #[error("Profile is missing")]
+    MissingProfile,
+    /// The user signed in to a Slack workspace other than the one the strategy is pinned to.
+    #[error("Slack workspace `{0}` is not allowed")]
+    SlackTeamMismatch(String),
+    #[error("OpenID assertion is invalid: {0}")]
+    InvalidOpenIdAssertion(String),
+    #[error("Telegram login data is invalid: {0}")]
+    InvalidTelegramData(String),
+    /// The instance is missing or is not a valid domain.
+    #[error("Instance `{0}` is invalid")]
+    InvalidInstance(String),
+    /// The strategy talks to the provider before redirecting the user, use
+    /// [`Passport::redirect_url_async`](crate::passport::Passport::redirect_url_async) instead.
+    #[error("Strategy must be authorized asynchronously")]
+    AsyncAuthorizationRequired,
+    /// No strategy is registered with the [`Choice`](crate::passport::Choice), e.g. an unknown provider in the
+    /// login route.
+    #[error("Strategy is not registered")]
+    MissingStrategy,
+    /// The client was not registered, or its registration cannot be managed.
+    #[error("Client registration is missing")]
+    MissingRegistration,
+    /// The user is not a member of the required Discord guild, or has none of the required roles.
+    #[error("Discord guild `{0}` membership is required")]
+    DiscordNotMember(String),
+    /// The Github user is not a member of any of the allowed organizations or teams.
+    #[error("Github user `{0}` is not a member of the allowed organizations or teams")]
+    GithubNotMember(String),
+    /// The user does not satisfy a [`Policy`](crate::policy::Policy) attached to the strategy.
+    #[error("Rejected by policy: {0}")]
+    PolicyRejected(String),
+    /// The verify callback of the strategy rejected the user.
+    #[error("Rejected by verify callback: {0}")]
+    VerifyRejected(String),
+    /// The strategy has no verify callback returning the user.
+    #[error("User is missing")]
+    MissingUser,
+    /// The session cookie or token is missing, forged or malformed, or the session key is invalid.
+    #[error("Session is invalid: {0}")]
+    InvalidSession(String),
+    #[error("Session has expired")]
+    SessionExpired,
+}
+
+impl Error {
+    /// A machine-readable reason of the failure, appended to the failure redirect when
+    /// [`Redirect::set_failure_reason`](crate::passport::Redirect::set_failure_reason) is set:
+    ///
+    /// * `access_denied`: the user denied the authorization.
+    /// * `invalid_state`: the state is missing or unknown, e.g. a replayed or forged callback.
+    /// * `not_member`: the user is not a member of the required organization, team, guild or workspace.
+    /// * `not_allowed`: a policy or the verify callback rejected the user.
+    /// * `invalid_request`: the data sent to the redirect url is invalid.
+    /// * `server_error`: anything else, e.g. the provider could not be reached.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::MissingAuthorizationCode => "access_denied",
+            Error::CSRFTokenMismatch
+            | Error::MissingCsrfToken
+            | Error::MissingAuthorizationCodeAndCsrfToken => "invalid_state",
+            Error::SlackTeamMismatch(_)
+            | Error::DiscordNotMember(_)
+            | Error::GithubNotMember(_) => "not_member",
+            Error::PolicyRejected(_) | Error::VerifyRejected(_) => "not_allowed",
+            Error::InvalidOpenIdAssertion(_)
+            | Error::InvalidTelegramData(_)
+            | Error::InvalidInstance(_)
+            | Error::MissingStrategy => "invalid_request",
+            _ => "server_error",
+        }
+    }
 }
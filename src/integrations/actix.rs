@@ -0,0 +1,346 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use actix_session::{Session, SessionExt};
+use actix_web::body::EitherBody;
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorUnauthorized;
+use actix_web::http::header;
+use actix_web::web::{self, Data, Form, Path, Query};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Scope};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::{AuthUser, LoginParams, STATE_COOKIE, STATE_COOKIE_MAX_AGE};
+use crate::error::Error;
+use crate::passport::{Choice, Passport, StateCode};
+
+/// The key of the [`AuthUser`] in the `actix-session` [`Session`].
+pub const IDENTITY_KEY: &str = "passport_user";
+
+/// The [`Passport`] shared by the routes of [`passport_scope`].
+pub type SharedPassport = Data<RwLock<Passport>>;
+
+/// Returns a scope mounted at `path` with the login and callback routes of every strategy of the `passport`:
+///
+/// * `GET {path}/{provider}` redirects the user to the provider, e.g. `/auth/github`, and sets the
+///   [`STATE_COOKIE`]. The Mastodon instance is given as the `instance` query parameter, and the page to return to
+///   once authenticated as the `return_to` query parameter.
+/// * `GET` and `POST {path}/{provider}/callback` check the state against the [`STATE_COOKIE`], authenticate the
+///   user, store the [`AuthUser`] in the session and redirect to the success redirect. The redirect url of the
+///   strategies must point there.
+///
+/// `{provider}` is the name of the [`Choice`], see [`Choice::from`]. The app must be wrapped in a
+/// `SessionMiddleware` of `actix-session`, with the store of its choice.
+///
+/// ```rust,ignore
+/// let passport = SharedPassport::new(RwLock::new(passport));
+/// HttpServer::new(move || {
+///     App::new()
+///         .wrap(SessionMiddleware::new(CookieSessionStore::default(), key.clone()))
+///         .service(passport_scope("/auth", passport.clone()))
+///         .service(web::resource("/dashboard").wrap(RequireAuth::new("/auth/github")).to(dashboard))
+/// });
+///
+/// async fn dashboard(user: AuthUser) -> impl Responder { .. }
+/// ```
+pub fn passport_scope(path: &str, passport: SharedPassport) -> Scope {
+    web::scope(path)
+        .app_data(passport)
+        .route("/{provider}", web::get().to(login))
+        .route("/{provider}/callback", web::get().to(callback_query))
+        .route("/{provider}/callback", web::post().to(callback_form))
+}
+
+fn redirect(url: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .finish()
+}
+
+/// Adds the `cookie` to the `response`.
+fn with_cookie(mut response: HttpResponse, cookie: &Cookie) -> HttpResponse {
+    match response.add_cookie(cookie) {
+        Ok(()) => response,
+        Err(error) => {
+            warn!(?error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// The state cookie, cleared when `state` is empty. It is scoped to the path of the scope, given the `login` route
+/// `{path}/{provider}`, and sent along with the cross-site `POST` of `response_mode=form_post`, hence
+/// `SameSite=None`.
+fn state_cookie(login: &str, state: &str) -> Cookie<'static> {
+    let path = match login.rsplit_once('/') {
+        Some((path, _)) if !path.is_empty() => path.to_string(),
+        _ => String::from("/"),
+    };
+    let max_age = if state.is_empty() {
+        0
+    } else {
+        STATE_COOKIE_MAX_AGE
+    };
+
+    Cookie::build(STATE_COOKIE, state.to_string())
+        .path(path)
+        .max_age(Duration::seconds(max_age as i64))
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::None)
+        .finish()
+}
+
+async fn login(
+    request: HttpRequest,
+    passport: SharedPassport,
+    provider: Path<String>,
+    params: Query<LoginParams>,
+) -> HttpResponse {
+    let choice = Choice::from(provider.as_str());
+    // The clone shares the logins in progress, the requests to the provider are sent without holding the lock.
+    let passport = passport.read().await.clone();
+    if !passport.contains(&choice) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    match passport
+        .authorize(
+            choice,
+            params.instance.as_deref(),
+            params.return_to.as_deref(),
+        )
+        .await
+    {
+        Ok((url, state)) if state.is_empty() => redirect(&url),
+        Ok((url, state)) => with_cookie(redirect(&url), &state_cookie(request.path(), &state)),
+        Err(error) => {
+            warn!(?error);
+            HttpResponse::BadGateway().finish()
+        }
+    }
+}
+
+async fn callback_query(
+    request: HttpRequest,
+    passport: SharedPassport,
+    provider: Path<String>,
+    Query(statecode): Query<StateCode>,
+    session: Session,
+) -> HttpResponse {
+    callback(request, passport, provider, statecode, session).await
+}
+
+/// The callback of `response_mode=form_post`.
+async fn callback_form(
+    request: HttpRequest,
+    passport: SharedPassport,
+    provider: Path<String>,
+    Form(statecode): Form<StateCode>,
+    session: Session,
+) -> HttpResponse {
+    callback(request, passport, provider, statecode, session).await
+}
+
+async fn callback(
+    request: HttpRequest,
+    passport: SharedPassport,
+    provider: Path<String>,
+    statecode: StateCode,
+    session: Session,
+) -> HttpResponse {
+    let choice = Choice::from(provider.as_str());
+    let passport = passport.read().await.clone();
+    if !passport.contains(&choice) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let login = request.path().strip_suffix("/callback").unwrap_or_default();
+    let clear = state_cookie(login, "");
+
+    // A state the browser didn't start the login with is a login CSRF.
+    if let Some(csrf_token) = &statecode.state {
+        let state = request.cookie(STATE_COOKIE);
+        if state.as_ref().map(Cookie::value) != Some(csrf_token.secret().as_str()) {
+            warn!("the state does not match the state cookie");
+            let failure_redirect = passport.failure_redirect(&choice, &Error::CSRFTokenMismatch);
+            return with_cookie(redirect(&failure_redirect), &clear);
+        }
+    }
+
+    let (response, redirect_url) = passport.authenticate(choice, statecode).await;
+
+    if let Some(response) = response {
+        // A fresh session id on login, against session fixation.
+        session.renew();
+        if let Err(error) = session.insert(IDENTITY_KEY, AuthUser::new(&provider, &response)) {
+            warn!(?error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    with_cookie(redirect(&redirect_url), &clear)
+}
+
+/// Extracts the signed in user from the session, or responds with `401 Unauthorized`.
+impl FromRequest for AuthUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user = request
+            .get_session()
+            .get::<AuthUser>(IDENTITY_KEY)
+            .ok()
+            .flatten();
+
+        ready(user.ok_or_else(|| ErrorUnauthorized("not signed in")))
+    }
+}
+
+/// Redirects the requests without a signed in user in their session to the `login_url`.
+#[derive(Clone)]
+pub struct RequireAuth {
+    login_url: String,
+}
+
+impl RequireAuth {
+    pub fn new(login_url: &str) -> Self {
+        Self {
+            login_url: login_url.to_string(),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RequireAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAuthMiddleware {
+            service,
+            login_url: self.login_url.clone(),
+        }))
+    }
+}
+
+/// The middleware of [`RequireAuth`].
+pub struct RequireAuthMiddleware<S> {
+    service: S,
+    login_url: String,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let signed_in = matches!(
+            request.get_session().get::<AuthUser>(IDENTITY_KEY),
+            Ok(Some(_))
+        );
+
+        if signed_in {
+            let response = self.service.call(request);
+            Box::pin(async move { response.await.map(ServiceResponse::map_into_left_body) })
+        } else {
+            let response = redirect(&self.login_url).map_into_right_body();
+            Box::pin(ready(Ok(request.into_response(response))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_session::storage::CookieSessionStore;
+    use actix_session::SessionMiddleware;
+    use actix_web::cookie::Key;
+    use actix_web::{test, App};
+
+    use super::*;
+    use crate::passport::Redirect;
+    use crate::strategies::GithubStrategy;
+
+    fn passport() -> SharedPassport {
+        let passport = Passport::default()
+            .redirect_urls(Redirect::new("https://ourco.com/login", "https://ourco.com/").unwrap())
+            .strategize(
+                Choice::Github,
+                GithubStrategy::new(
+                    "id",
+                    "secret",
+                    &[],
+                    "https://ourco.com/auth/github/callback",
+                ),
+            )
+            .unwrap();
+
+        SharedPassport::new(RwLock::new(passport))
+    }
+
+    #[actix_web::test]
+    async fn binds_the_state_to_the_browser() {
+        let app = test::init_service(
+            App::new()
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .service(passport_scope("/auth", passport())),
+        )
+        .await;
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/auth/github").to_request(),
+        )
+        .await;
+        let cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == STATE_COOKIE)
+            .unwrap()
+            .into_owned();
+        assert_eq!(cookie.path(), Some("/auth"));
+        assert_eq!(cookie.same_site(), Some(SameSite::None));
+        assert_eq!(cookie.secure(), Some(true));
+
+        // The callback of a login the browser did not start, e.g. a link sent by an attacker.
+        let request = test::TestRequest::get()
+            .uri(&format!(
+                "/auth/github/callback?state={}&code=code",
+                cookie.value()
+            ))
+            .cookie(Cookie::new(STATE_COOKIE, "attacker"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://ourco.com/login"
+        );
+        // No session is started, its cookie is named `id` by default.
+        assert!(response
+            .response()
+            .cookies()
+            .all(|cookie| cookie.name() != "id"));
+    }
+}
@@ -0,0 +1,207 @@
+use ::axum::extract::{Form, FromRequest, FromRequestParts, Query, Request};
+use ::axum::http::request::Parts;
+use ::axum::http::{Method, StatusCode};
+use ::axum::response::{IntoResponse, Response};
+use ::axum::routing::any;
+use ::axum::Router;
+use async_trait::async_trait;
+
+use super::tower::PassportLayer;
+use super::AuthUser;
+use crate::passport::StateCode;
+
+pub use super::tower::{RequireAuth, RequireAuthService, SharedPassport};
+
+/// Mounts the login and callback routes of every strategy of the `passport`, handled by the [`PassportLayer`]:
+///
+/// * `GET /auth/:provider` redirects the user to the provider, e.g. `/auth/github`. The Mastodon instance is given
+///   as the `instance` query parameter, and the page to return to once authenticated as the `return_to` query
+///   parameter.
+/// * `GET` and `POST /auth/:provider/callback` authenticate the user, start a session and redirect to the success
+///   redirect. The redirect url of the strategies must point there.
+/// * `GET` and `POST /auth/logout` end the session, clear its cookie and redirect to `/`.
+///
+/// `:provider` is the name of the [`Choice`](crate::passport::Choice), see
+/// [`Choice::from`](crate::passport::Choice::from).
+///
+/// ```rust,ignore
+/// let passport = SharedPassport::from(passport);
+/// let app = Router::new()
+///     .route("/dashboard", get(dashboard))
+///     .route_layer(RequireAuth::new(&passport, "/auth/github"))
+///     .merge(passport_router(passport));
+///
+/// async fn dashboard(user: AuthUser) -> impl IntoResponse { .. }
+/// ```
+pub fn passport_router<P>(passport: P) -> Router
+where
+    P: Into<SharedPassport>,
+{
+    // The layer answers these routes itself.
+    Router::new()
+        .route("/auth/:provider", any(StatusCode::NOT_FOUND))
+        .route("/auth/:provider/callback", any(StatusCode::NOT_FOUND))
+        .route_layer(PassportLayer::new(passport))
+}
+
+/// Extracts the [`StateCode`] from the query of a `GET` request, or from the form body of a `POST` request
+/// (`response_mode=form_post`).
+#[async_trait]
+impl<S> FromRequest<S> for StateCode
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if request.method() == Method::POST {
+            Form::<StateCode>::from_request(request, state)
+                .await
+                .map(|Form(statecode)| statecode)
+                .map_err(IntoResponse::into_response)
+        } else {
+            Query::<StateCode>::try_from_uri(request.uri())
+                .map(|Query(statecode)| statecode)
+                .map_err(IntoResponse::into_response)
+        }
+    }
+}
+
+/// Extracts the signed in user on the routes behind [`RequireAuth`].
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::axum::body::{to_bytes, Body};
+    use ::axum::http::header;
+    use ::axum::routing::get;
+    use serde_json::json;
+    use tower_service::Service;
+
+    use super::*;
+    use crate::integrations::SESSION_COOKIE;
+    use crate::passport::{Choice, Passport, Redirect};
+    use crate::strategies::GithubStrategy;
+
+    fn passport() -> SharedPassport {
+        Passport::default()
+            .redirect_urls(Redirect::new("https://ourco.com/login", "https://ourco.com/").unwrap())
+            .strategize(
+                Choice::Github,
+                GithubStrategy::new(
+                    "id",
+                    "secret",
+                    &[],
+                    "https://ourco.com/auth/github/callback",
+                ),
+            )
+            .unwrap()
+            .into()
+    }
+
+    fn request(method: Method, uri: &str, cookie: Option<&str>, body: &str) -> Request {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        if !body.is_empty() {
+            request = request.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn call(router: &mut Router, request: Request) -> (StatusCode, String) {
+        let response = router.call(request).await.unwrap();
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|location| location.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (
+            status,
+            location.unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn extracts_the_statecode_from_the_query_or_the_form() {
+        async fn code(statecode: StateCode) -> String {
+            statecode.code.unwrap_or_default()
+        }
+        let mut router = Router::new().route("/callback", get(code).post(code));
+
+        let query = request(Method::GET, "/callback?state=state&code=query", None, "");
+        assert_eq!(
+            call(&mut router, query).await,
+            (StatusCode::OK, String::from("query"))
+        );
+
+        let form = request(Method::POST, "/callback", None, "state=state&code=form");
+        assert_eq!(
+            call(&mut router, form).await,
+            (StatusCode::OK, String::from("form"))
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_the_require_auth_layer() {
+        async fn me(user: AuthUser) -> String {
+            user.profile["login"].to_string()
+        }
+        let passport = passport();
+        let id = passport.sessions().insert(AuthUser {
+            provider: String::from("github"),
+            profile: json!({ "login": "ada" }),
+            roles: Vec::new(),
+        });
+        let cookie = format!("{SESSION_COOKIE}={id}");
+
+        // The user is only extracted once `RequireAuth` checked the session.
+        let mut router = Router::new().route("/me", get(me));
+        let response = call(&mut router, request(Method::GET, "/me", Some(&cookie), "")).await;
+        assert_eq!(response.0, StatusCode::UNAUTHORIZED);
+
+        let mut router = router.route_layer(RequireAuth::new(&passport, "/auth/github"));
+        let response = call(&mut router, request(Method::GET, "/me", Some(&cookie), "")).await;
+        assert_eq!(response, (StatusCode::OK, String::from("\"ada\"")));
+    }
+
+    #[tokio::test]
+    async fn answers_the_routes_through_the_layer() {
+        let mut router = passport_router(passport());
+
+        let (status, location) =
+            call(&mut router, request(Method::GET, "/auth/github", None, "")).await;
+        assert_eq!(status, StatusCode::FOUND);
+        assert!(location.starts_with("https://github.com/login/oauth/authorize"));
+
+        let uri = "/auth/github/callback?state=state&code=code";
+        let response = call(&mut router, request(Method::GET, uri, None, "")).await;
+        assert_eq!(
+            response,
+            (StatusCode::FOUND, String::from("https://ourco.com/login"))
+        );
+
+        let response = call(&mut router, request(Method::GET, "/auth/logout", None, "")).await;
+        assert_eq!(response, (StatusCode::FOUND, String::from("/")));
+
+        let response = call(&mut router, request(Method::GET, "/auth/gitlab", None, "")).await;
+        assert_eq!(response.0, StatusCode::NOT_FOUND);
+    }
+}
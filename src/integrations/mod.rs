@@ -0,0 +1,213 @@
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use oauth2::CsrfToken;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::passport::{now, Oauth2ServerResponse, LOGIN_MAX_AGE};
+
+/// The name of the cookie holding the session of the signed in user.
+pub const SESSION_COOKIE: &str = "passport_session";
+
+/// The name of the cookie binding the state of a login to the browser that started it.
+pub const STATE_COOKIE: &str = "passport_state";
+
+/// How long the state cookie lasts, in seconds: as long as the login is kept in progress by the passport.
+pub(crate) const STATE_COOKIE_MAX_AGE: u64 = LOGIN_MAX_AGE;
+
+/// How long a session lasts by default, in seconds.
+pub(crate) const SESSION_MAX_AGE: u64 = 24 * 60 * 60;
+
+/// How many sessions are kept at most by default.
+pub(crate) const MAX_SESSIONS: usize = 10_000;
+
+/// The user signed in through an integration, kept in its session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthUser {
+    /// The provider the user signed in with, as in the `/auth/:provider` routes.
+    pub provider: String,
+    pub profile: Value,
+    pub roles: Vec<String>,
+}
+
+impl AuthUser {
+    pub fn new(provider: &str, response: &Oauth2ServerResponse) -> Self {
+        Self {
+            provider: provider.to_string(),
+            profile: response.profile.clone(),
+            roles: response.roles.clone(),
+        }
+    }
+}
+
+/// The sessions of the signed in users, kept in memory and shared among the clones.
+///
+/// A session lasts a day by default, see [`Sessions::set_max_age`]. At most 10000 sessions are kept by default,
+/// see [`Sessions::set_capacity`]; the oldest one is ended to make room.
+#[derive(Clone)]
+pub struct Sessions {
+    users: Arc<Mutex<Users>>,
+    max_age: u64,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct Users {
+    /// The users along with the expiry of their session, in seconds since the epoch.
+    sessions: HashMap<String, (AuthUser, u64)>,
+    /// The session ids, the oldest first.
+    order: VecDeque<String>,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self {
+            users: Arc::new(Mutex::new(Users::default())),
+            max_age: SESSION_MAX_AGE,
+            capacity: MAX_SESSIONS,
+        }
+    }
+}
+
+impl Sessions {
+    /// Sets how long a session lasts, in seconds.
+    pub fn set_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Sets how many sessions are kept at most.
+    pub fn set_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn max_age(&self) -> u64 {
+        self.max_age
+    }
+
+    /// Starts a session for the `user`, returning its id.
+    pub fn insert(&self, user: AuthUser) -> String {
+        let id = CsrfToken::new_random().secret().to_string();
+        let now = now();
+
+        let mut users = self.users.lock().unwrap();
+        let users = &mut *users;
+        users
+            .sessions
+            .insert(id.clone(), (user, now + self.max_age));
+        users.order.push_back(id.clone());
+
+        // The ids are in the order of expiry, the ended sessions are skipped.
+        while let Some(oldest) = users.order.front() {
+            let expired = match users.sessions.get(oldest) {
+                Some((_, expires)) => *expires <= now,
+                None => true,
+            };
+            if !expired && users.sessions.len() <= self.capacity {
+                break;
+            }
+            if let Some(oldest) = users.order.pop_front() {
+                users.sessions.remove(&oldest);
+            }
+        }
+        id
+    }
+
+    /// Returns the user of the session, unless it has expired.
+    pub fn get(&self, id: &str) -> Option<AuthUser> {
+        let mut users = self.users.lock().unwrap();
+        match users.sessions.get(id) {
+            Some((user, expires)) if *expires > now() => Some(user.clone()),
+            Some(_) => {
+                users.sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Ends the session, e.g. on logout.
+    pub fn remove(&self, id: &str) -> Option<AuthUser> {
+        self.users
+            .lock()
+            .unwrap()
+            .sessions
+            .remove(id)
+            .map(|(user, _)| user)
+    }
+}
+
+/// The query of the login routes.
+#[cfg(feature = "actix")]
+#[derive(Deserialize)]
+pub(crate) struct LoginParams {
+    /// The Mastodon instance of the user.
+    pub(crate) instance: Option<String>,
+    /// The page to return to once authenticated, see [`Passport::redirect_url`](crate::passport::Passport::redirect_url).
+    pub(crate) return_to: Option<String>,
+}
+
+/// Finds the cookie `name` in a `Cookie` header.
+#[cfg(feature = "tower")]
+pub(crate) fn cookie<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// The `Set-Cookie` header value of the session cookie lasting `max_age` seconds, cleared when `id` is empty.
+#[cfg(feature = "tower")]
+pub(crate) fn session_cookie(id: &str, max_age: u64) -> String {
+    let max_age = if id.is_empty() { 0 } else { max_age };
+    format!("{SESSION_COOKIE}={id}; Path=/; Max-Age={max_age}; HttpOnly; Secure; SameSite=Lax")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn user(login: &str) -> AuthUser {
+        AuthUser {
+            provider: String::from("github"),
+            profile: json!({ "login": login }),
+            roles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn expires_the_sessions() {
+        let sessions = Sessions::default().set_max_age(0);
+        let id = sessions.insert(user("octocat"));
+
+        assert!(sessions.get(&id).is_none());
+        assert!(sessions.users.lock().unwrap().sessions.is_empty());
+    }
+
+    #[test]
+    fn ends_the_oldest_session() {
+        let sessions = Sessions::default().set_capacity(2);
+        let first = sessions.insert(user("first"));
+        let second = sessions.insert(user("second"));
+        let third = sessions.insert(user("third"));
+
+        assert!(sessions.get(&first).is_none());
+        assert_eq!(sessions.get(&second).unwrap().profile["login"], "second");
+        assert_eq!(sessions.get(&third).unwrap().profile["login"], "third");
+
+        assert!(sessions.remove(&second).is_some());
+        assert!(sessions.get(&second).is_none());
+    }
+}
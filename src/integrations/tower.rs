@@ -0,0 +1,630 @@
+use std::error::Error as StdError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::{header, Method, Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Limited};
+use oauth2::url::form_urlencoded;
+use serde_json::{Map, Value};
+use tokio::sync::RwLock;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::warn;
+
+use super::{
+    cookie, session_cookie, AuthUser, Sessions, SESSION_COOKIE, STATE_COOKIE, STATE_COOKIE_MAX_AGE,
+};
+use crate::error::Error;
+use crate::passport::{Choice, Passport, StateCode};
+
+/// The largest form body accepted on the callback route.
+const MAX_FORM_LENGTH: usize = 64 * 1024;
+
+/// The [`Passport`] shared by the routes, along with the [`Sessions`] of the signed in users.
+#[derive(Clone)]
+pub struct SharedPassport {
+    passport: Arc<RwLock<Passport>>,
+    sessions: Sessions,
+}
+
+impl From<Passport> for SharedPassport {
+    fn from(passport: Passport) -> Self {
+        Self {
+            passport: Arc::new(RwLock::new(passport)),
+            sessions: Sessions::default(),
+        }
+    }
+}
+
+impl SharedPassport {
+    pub fn passport(&self) -> &Arc<RwLock<Passport>> {
+        &self.passport
+    }
+
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
+    /// Sets the [`Sessions`] of the signed in users, e.g. to change how long they last.
+    pub fn set_sessions(mut self, sessions: Sessions) -> Self {
+        self.sessions = sessions;
+        self
+    }
+}
+
+/// Handles the login and callback routes of every strategy of the `passport` in front of any tower service, and
+/// passes the other requests through:
+///
+/// * `GET {path}/{provider}` redirects the user to the provider, e.g. `/auth/github`, and sets the
+///   [`STATE_COOKIE`]. The Mastodon instance is given as the `instance` query parameter, and the page to return to
+///   once authenticated as the `return_to` query parameter.
+/// * `GET` and `POST {path}/{provider}/callback` check the state against the [`STATE_COOKIE`], authenticate the
+///   user, start a session and redirect to the success redirect. The redirect url of the strategies must point
+///   there.
+/// * `GET` and `POST {path}/logout` end the session, clear its cookie and redirect to `/`.
+///
+/// `{provider}` is the name of the [`Choice`], see [`Choice::from`], and `{path}` is `/auth` unless set otherwise.
+///
+/// ```rust,ignore
+/// let passport = SharedPassport::from(passport);
+/// let service = ServiceBuilder::new()
+///     .layer(PassportLayer::new(passport.clone()))
+///     .service(app);
+/// ```
+#[derive(Clone)]
+pub struct PassportLayer {
+    shared: SharedPassport,
+    path: String,
+}
+
+impl PassportLayer {
+    pub fn new<P>(passport: P) -> Self
+    where
+        P: Into<SharedPassport>,
+    {
+        Self {
+            shared: passport.into(),
+            path: String::from("/auth"),
+        }
+    }
+
+    /// Sets the path the routes are mounted at, `/auth` by default.
+    pub fn set_path(mut self, path: &str) -> Self {
+        self.path = path.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+impl<S> Layer<S> for PassportLayer {
+    type Service = PassportService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PassportService {
+            inner,
+            shared: self.shared.clone(),
+            path: self.path.clone(),
+        }
+    }
+}
+
+/// The service of [`PassportLayer`].
+#[derive(Clone)]
+pub struct PassportService<S> {
+    inner: S,
+    shared: SharedPassport,
+    path: String,
+}
+
+enum Route {
+    Login(String),
+    Callback(String),
+    Logout,
+}
+
+impl<S> PassportService<S> {
+    fn route(&self, path: &str) -> Option<Route> {
+        let rest = path.strip_prefix(&self.path)?.strip_prefix('/')?;
+
+        match rest.split_once('/') {
+            None if rest == "logout" => Some(Route::Logout),
+            None if !rest.is_empty() => Some(Route::Login(rest.to_string())),
+            Some((provider, "callback")) if !provider.is_empty() => {
+                Some(Route::Callback(provider.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for PassportService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    ResBody: From<String> + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let shared = self.shared.clone();
+        let path = self.path.clone();
+
+        match self.route(request.uri().path()) {
+            Some(Route::Login(provider)) => {
+                Box::pin(async move { Ok(login(shared, &path, &provider, request).await) })
+            }
+            Some(Route::Callback(provider)) => {
+                Box::pin(async move { Ok(callback(shared, &path, &provider, request).await) })
+            }
+            Some(Route::Logout) => Box::pin(async move { Ok(logout(shared, request)) }),
+            None => Box::pin(self.inner.call(request)),
+        }
+    }
+}
+
+async fn login<B, ResBody>(
+    shared: SharedPassport,
+    path: &str,
+    provider: &str,
+    request: Request<B>,
+) -> Response<ResBody>
+where
+    ResBody: From<String>,
+{
+    let choice = Choice::from(provider);
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let param = |key: &str| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    };
+    let (instance, return_to) = (param("instance"), param("return_to"));
+
+    // The clone shares the logins in progress, the requests to the provider are sent without holding the lock.
+    let passport = shared.passport.read().await.clone();
+    if !passport.contains(&choice) {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    match passport
+        .authorize(choice, instance.as_deref(), return_to.as_deref())
+        .await
+    {
+        Ok((url, state)) if state.is_empty() => redirect(&url, &[]),
+        Ok((url, state)) => redirect(&url, &[state_cookie(path, &state)]),
+        Err(error) => {
+            warn!(?error);
+            status(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
+
+async fn callback<B, ResBody>(
+    shared: SharedPassport,
+    path: &str,
+    provider: &str,
+    request: Request<B>,
+) -> Response<ResBody>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    ResBody: From<String>,
+{
+    let choice = Choice::from(provider);
+    let state = request
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| cookie(header, STATE_COOKIE))
+        .map(String::from);
+
+    let statecode = match statecode(request).await {
+        Ok(statecode) => statecode,
+        Err(error) => {
+            warn!(?error);
+            return status(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let passport = shared.passport.read().await.clone();
+    if !passport.contains(&choice) {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    let clear = state_cookie(path, "");
+    // A state the browser didn't start the login with is a login CSRF.
+    if let Some(csrf_token) = &statecode.state {
+        if state.as_deref() != Some(csrf_token.secret().as_str()) {
+            warn!("the state does not match the state cookie");
+            let failure_redirect = passport.failure_redirect(&choice, &Error::CSRFTokenMismatch);
+            return redirect(&failure_redirect, &[clear]);
+        }
+    }
+
+    let (response, redirect_url) = passport.authenticate(choice, statecode).await;
+
+    match response {
+        Some(response) => {
+            let id = shared.sessions.insert(AuthUser::new(provider, &response));
+            let max_age = shared.sessions.max_age();
+            redirect(&redirect_url, &[session_cookie(&id, max_age), clear])
+        }
+        None => redirect(&redirect_url, &[clear]),
+    }
+}
+
+/// Ends the session of the request and clears its cookie.
+fn logout<B, ResBody>(shared: SharedPassport, request: Request<B>) -> Response<ResBody>
+where
+    ResBody: From<String>,
+{
+    if let Some(id) = request
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| cookie(header, SESSION_COOKIE))
+    {
+        shared.sessions.remove(id);
+    }
+
+    redirect("/", &[session_cookie("", 0)])
+}
+
+/// Reads the [`StateCode`] from the query of a `GET` request, or from the form body of a `POST` request
+/// (`response_mode=form_post`).
+async fn statecode<B>(request: Request<B>) -> Result<StateCode, String>
+where
+    B: Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let form = if request.method() == Method::POST {
+        Limited::new(request.into_body(), MAX_FORM_LENGTH)
+            .collect()
+            .await
+            .map_err(|error| error.to_string())?
+            .to_bytes()
+            .to_vec()
+    } else {
+        request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .as_bytes()
+            .to_vec()
+    };
+
+    let params = form_urlencoded::parse(&form)
+        .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+        .collect::<Map<_, _>>();
+
+    serde_json::from_value(Value::Object(params)).map_err(|error| error.to_string())
+}
+
+/// The `Set-Cookie` header value of the state cookie, cleared when `state` is empty. It is sent along with the
+/// cross-site `POST` of `response_mode=form_post`, hence `SameSite=None`.
+fn state_cookie(path: &str, state: &str) -> String {
+    let max_age = if state.is_empty() {
+        0
+    } else {
+        STATE_COOKIE_MAX_AGE
+    };
+    format!(
+        "{STATE_COOKIE}={state}; Path={path}; Max-Age={max_age}; HttpOnly; Secure; SameSite=None"
+    )
+}
+
+fn redirect<ResBody>(url: &str, cookies: &[String]) -> Response<ResBody>
+where
+    ResBody: From<String>,
+{
+    let mut response = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, url);
+    for cookie in cookies {
+        response = response.header(header::SET_COOKIE, cookie);
+    }
+
+    response
+        .body(ResBody::from(String::new()))
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status<ResBody>(status: StatusCode) -> Response<ResBody>
+where
+    ResBody: From<String>,
+{
+    let mut response = Response::new(ResBody::from(String::new()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Redirects the requests without a session to the `login_url`, and makes the [`AuthUser`] of the others
+/// available to the handlers as a request extension.
+#[derive(Clone)]
+pub struct RequireAuth {
+    sessions: Sessions,
+    login_url: String,
+}
+
+impl RequireAuth {
+    pub fn new(passport: &SharedPassport, login_url: &str) -> Self {
+        Self {
+            sessions: passport.sessions.clone(),
+            login_url: login_url.to_string(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequireAuth {
+    type Service = RequireAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireAuthService {
+            inner,
+            sessions: self.sessions.clone(),
+            login_url: self.login_url.clone(),
+        }
+    }
+}
+
+/// The service of [`RequireAuth`].
+#[derive(Clone)]
+pub struct RequireAuthService<S> {
+    inner: S,
+    sessions: Sessions,
+    login_url: String,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RequireAuthService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    ResBody: From<String> + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let user = request
+            .headers()
+            .get(header::COOKIE)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| cookie(header, SESSION_COOKIE))
+            .and_then(|id| self.sessions.get(id));
+
+        match user {
+            Some(user) => {
+                request.extensions_mut().insert(user);
+                Box::pin(self.inner.call(request))
+            }
+            None => {
+                let redirect = redirect(&self.login_url, &[]);
+                Box::pin(async move { Ok(redirect) })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::future::{ready, Ready};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::passport::Redirect;
+    use crate::strategies::GithubStrategy;
+    use crate::testing::serve;
+
+    /// The app behind the layers, answering with the login of the signed in user, if any.
+    #[derive(Clone)]
+    struct App;
+
+    impl Service<Request<String>> for App {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<String>) -> Self::Future {
+            let login = request
+                .extensions()
+                .get::<AuthUser>()
+                .map(|user| user.profile["login"].to_string())
+                .unwrap_or_default();
+
+            ready(Ok(Response::new(login)))
+        }
+    }
+
+    /// A passport whose Github strategy talks to a fake provider.
+    async fn passport() -> SharedPassport {
+        let (url, _) = serve(vec![
+            (
+                "/token",
+                200,
+                json!({ "access_token": "access", "token_type": "Bearer" }),
+            ),
+            ("/user", 200, json!({ "login": "ada" })),
+        ])
+        .await;
+
+        let mut strategy = GithubStrategy::new(
+            "id",
+            "secret",
+            &[],
+            "https://ourco.com/auth/github/callback",
+        );
+        strategy.token_uri = format!("{url}/token");
+        strategy.request_uri = format!("{url}/user");
+
+        let redirects = Redirect::new("https://ourco.com/login", "https://ourco.com/")
+            .unwrap()
+            .set_return_to_allowlist(&["/app"]);
+        Passport::default()
+            .redirect_urls(redirects)
+            .strategize(Choice::Github, strategy)
+            .unwrap()
+            .into()
+    }
+
+    fn request(method: Method, uri: &str, cookie: Option<&str>, body: &str) -> Request<String> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        if !body.is_empty() {
+            request = request.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        }
+        request.body(body.to_string()).unwrap()
+    }
+
+    async fn call<S>(service: &mut S, request: Request<String>) -> Response<String>
+    where
+        S: Service<Request<String>, Response = Response<String>, Error = Infallible>,
+    {
+        service.call(request).await.unwrap()
+    }
+
+    fn location(response: &Response<String>) -> &str {
+        response.headers()[header::LOCATION].to_str().unwrap()
+    }
+
+    /// The value of the cookie `name` set by the `response`.
+    fn set_cookie<'a>(response: &'a Response<String>, name: &str) -> Option<&'a str> {
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|header| header.to_str().ok())
+            .find_map(|header| cookie(header.split(';').next()?, name))
+    }
+
+    /// Starts a login at `uri`, returning the state bound to the browser.
+    async fn login(service: &mut PassportService<App>, uri: &str) -> String {
+        let response = call(service, request(Method::GET, uri, None, "")).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert!(location(&response).starts_with("https://github.com/login/oauth/authorize"));
+
+        let header = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(header.contains("Path=/auth; Max-Age=600; HttpOnly; Secure; SameSite=None"));
+        set_cookie(&response, STATE_COOKIE).unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn binds_the_state_to_the_browser() {
+        let shared = passport().await;
+        let mut service = PassportLayer::new(shared.clone()).layer(App);
+        let state = login(&mut service, "/auth/github").await;
+        let uri = format!("/auth/github/callback?state={state}&code=code");
+
+        // The callback of a login the browser did not start, e.g. a link sent by an attacker.
+        for cookie in [None, Some("passport_state=attacker")] {
+            let response = call(&mut service, request(Method::GET, &uri, cookie, "")).await;
+            assert_eq!(location(&response), "https://ourco.com/login");
+            assert_eq!(set_cookie(&response, STATE_COOKIE), Some(""));
+            assert_eq!(set_cookie(&response, SESSION_COOKIE), None);
+        }
+
+        let cookie = format!("{STATE_COOKIE}={state}");
+        let response = call(&mut service, request(Method::GET, &uri, Some(&cookie), "")).await;
+        assert_eq!(location(&response), "https://ourco.com/");
+        let id = set_cookie(&response, SESSION_COOKIE).unwrap();
+        assert_eq!(shared.sessions().get(id).unwrap().profile["login"], "ada");
+    }
+
+    #[tokio::test]
+    async fn reads_the_form_post_callback() {
+        let shared = passport().await;
+        let mut service = PassportLayer::new(shared.clone()).layer(App);
+        let state = login(&mut service, "/auth/github").await;
+
+        let cookie = format!("{STATE_COOKIE}={state}");
+        let body = format!("state={state}&code=code");
+        let request = request(Method::POST, "/auth/github/callback", Some(&cookie), &body);
+        let response = call(&mut service, request).await;
+        assert_eq!(location(&response), "https://ourco.com/");
+        assert!(set_cookie(&response, SESSION_COOKIE).is_some());
+    }
+
+    #[tokio::test]
+    async fn returns_to_the_allowed_page() {
+        let mut service = PassportLayer::new(passport().await).layer(App);
+        let state = login(&mut service, "/auth/github?return_to=%2Fapp%2Fsettings").await;
+
+        let uri = format!("/auth/github/callback?state={state}&code=code");
+        let cookie = format!("{STATE_COOKIE}={state}");
+        let response = call(&mut service, request(Method::GET, &uri, Some(&cookie), "")).await;
+        assert_eq!(location(&response), "https://ourco.com/app/settings");
+    }
+
+    #[tokio::test]
+    async fn logs_out() {
+        let shared = passport().await;
+        let mut service = PassportLayer::new(shared.clone()).layer(App);
+        let id = shared.sessions().insert(AuthUser {
+            provider: String::from("github"),
+            profile: json!({ "login": "ada" }),
+            roles: Vec::new(),
+        });
+
+        let cookie = format!("{SESSION_COOKIE}={id}");
+        let response = call(
+            &mut service,
+            request(Method::POST, "/auth/logout", Some(&cookie), ""),
+        )
+        .await;
+        assert_eq!(location(&response), "/");
+        assert_eq!(
+            response.headers()[header::SET_COOKIE],
+            "passport_session=; Path=/; Max-Age=0; HttpOnly; Secure; SameSite=Lax"
+        );
+        assert!(shared.sessions().get(&id).is_none());
+    }
+
+    #[tokio::test]
+    async fn requires_a_session() {
+        let shared = passport().await;
+        let mut service = RequireAuth::new(&shared, "/auth/github").layer(App);
+
+        let response = call(&mut service, request(Method::GET, "/app", None, "")).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(location(&response), "/auth/github");
+
+        let id = shared.sessions().insert(AuthUser {
+            provider: String::from("github"),
+            profile: json!({ "login": "ada" }),
+            roles: Vec::new(),
+        });
+        let cookie = format!("{SESSION_COOKIE}={id}");
+        let response = call(
+            &mut service,
+            request(Method::GET, "/app", Some(&cookie), ""),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "\"ada\"");
+    }
+}
@@ -1,49 +1,141 @@
 use oauth2::basic::BasicClient;
-use oauth2::reqwest::async_http_client;
-use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
-    PkceCodeVerifier, RedirectUrl, TokenResponse,
-};
+use oauth2::{AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl};
 use reqwest::Url;
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{info, warn};
 
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::Error;
-use crate::strategies::{PAccessToken, PRefreshToken, Strategy};
+use crate::policy::Policy;
+use crate::roles::RoleMapping;
+use crate::strategies::{PAccessToken, PRefreshToken, PTokenSecret, Strategy, TokenSet};
 
+/// The seconds elapsed since the Unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The `state` and `code` sent by the provider to the redirect url.
+///
+/// Depending on the [`ResponseMode`] of the strategy, they are delivered either as query parameters of a `GET`
+/// request or as the form body of a `POST` request. `StateCode` deserializes from both.
+///
+/// ```rust,ignore
+/// // axum
+/// async fn callback(Query(statecode): Query<StateCode>) -> impl IntoResponse { .. }
+/// async fn form_post_callback(Form(statecode): Form<StateCode>) -> impl IntoResponse { .. }
+///
+/// // actix-web
+/// async fn callback(web::Query(statecode): web::Query<StateCode>) -> HttpResponse { .. }
+/// async fn form_post_callback(web::Form(statecode): web::Form<StateCode>) -> HttpResponse { .. }
+/// ```
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct StateCode {
-    state: Option<CsrfToken>,
-    code: Option<String>,
+    pub(crate) state: Option<CsrfToken>,
+    pub(crate) code: Option<String>,
+    /// The user's name and email (a JSON string), posted by Apple on the first authorization only.
+    pub(crate) user: Option<String>,
+    /// Any other parameter sent to the redirect url, e.g. the `openid.*` parameters of Steam.
+    #[serde(flatten)]
+    pub(crate) params: HashMap<String, String>,
+}
+
+/// How the provider delivers the [`StateCode`] to the redirect url.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ResponseMode {
+    /// Query parameters of a `GET` request.
+    #[default]
+    Query,
+    /// Form body of a `POST` request (`response_mode=form_post`). The state is kept by the [`Passport`] rather than
+    /// in a cookie, so the flow is not affected by `SameSite=Lax` cookies being left out of the cross-site `POST`.
+    /// Any cookie your application needs on the redirect url route must be set with `SameSite=None; Secure`.
+    FormPost,
 }
 
+#[derive(Clone)]
 pub struct Redirect {
     failure_redirect: Url,
     success_redirect: Url,
+    /// The origins and path prefixes a `return_to` may point to.
+    allowlist: Vec<String>,
+    /// Whether the reason of a failure is appended to the failure redirect.
+    failure_reason: bool,
 }
 
 impl Redirect {
     pub fn new(failure_redirect: &str, success_redirect: &str) -> Result<Self, Error> {
         let redirect = failure_redirect
             .parse::<reqwest::Url>()
-            .map_err(Error::ParseError)
-            .map(|r| r)?;
+            .map_err(Error::ParseError)?;
         let success = success_redirect
             .parse::<reqwest::Url>()
-            .map_err(Error::ParseError)
-            .map(|r| r)?;
+            .map_err(Error::ParseError)?;
 
         Ok(Self {
             failure_redirect: redirect,
             success_redirect: success,
+            allowlist: Vec::new(),
+            failure_reason: false,
         })
     }
+
+    /// Appends the reason of a failure to the failure redirect as the `error` query parameter, e.g.
+    /// `?error=access_denied` or `?error=not_member`, for the frontend to show the right message.
+    /// See [`Error::reason`] for the reasons.
+    pub fn set_failure_reason(mut self, failure_reason: bool) -> Self {
+        self.failure_reason = failure_reason;
+        self
+    }
+
+    /// The failure redirect for `error`.
+    fn failure(&self, error: &Error) -> Url {
+        let mut url = self.failure_redirect.clone();
+        if self.failure_reason {
+            url.query_pairs_mut().append_pair("error", error.reason());
+        }
+        url
+    }
+
+    /// Sets where the `return_to` given to [`Passport::redirect_url`] may send the user once authenticated,
+    /// either origins (`https://admin.ourco.com`) or path prefixes of the origin of the success redirect
+    /// (`/dashboard`). Any other `return_to` falls back to the success redirect, none are allowed by default.
+    pub fn set_return_to_allowlist(mut self, allowlist: &[&str]) -> Self {
+        self.allowlist = allowlist.iter().map(|entry| entry.to_string()).collect();
+        self
+    }
+
+    /// Resolves `return_to` against the success redirect, and returns it when the allowlist admits it.
+    fn return_to(&self, return_to: &str) -> Option<Url> {
+        let url = self.success_redirect.join(return_to).ok()?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+
+        self.allowlist
+            .iter()
+            .filter_map(|entry| self.success_redirect.join(entry).ok())
+            .any(|entry| {
+                let prefix = entry.path();
+                let rest = url.path().strip_prefix(prefix);
+                // `/app` admits `/app` and `/app/settings`, not `/application`.
+                entry.origin() == url.origin()
+                    && rest.is_some_and(|rest| {
+                        prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+                    })
+            })
+            .then_some(url)
+    }
 }
 
+/// The key a [`Strategy`] is registered with on the [`Passport`].
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub enum Choice {
     Github,
@@ -53,65 +145,351 @@ pub enum Choice {
     Discord,
     FortyTwo,
     Reddit,
+    Apple,
+    Twitter,
+    Gitlab,
+    Linkedin,
+    Twitch,
+    Slack,
+    Steam,
+    Telegram,
+    Mastodon,
+    Atlassian,
+    /// Any other key, e.g. to register a strategy for more than one instance of a provider:
+    ///
+    /// ```rust,ignore
+    /// let passport = Passport::default()
+    ///     .strategize(Choice::Gitlab, GitlabStrategy::new(..))?
+    ///     .strategize(
+    ///         Choice::Custom("gitlab-internal".into()),
+    ///         GitlabStrategy::new(..).set_base_url("https://gitlab.example.com"),
+    ///     )?;
+    /// ```
+    Custom(String),
+}
+
+/// The choice named `name`, as in the `/auth/:provider` routes of the integrations: e.g. `github`, `google` or
+/// `x`. Any other name is a [`Choice::Custom`].
+impl From<&str> for Choice {
+    fn from(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "github" => Choice::Github,
+            "google" => Choice::Google,
+            "microsoft" => Choice::Microsoft,
+            "facebook" => Choice::Facebook,
+            "discord" => Choice::Discord,
+            "fortytwo" | "42" => Choice::FortyTwo,
+            "reddit" => Choice::Reddit,
+            "apple" => Choice::Apple,
+            "twitter" | "x" => Choice::Twitter,
+            "gitlab" => Choice::Gitlab,
+            "linkedin" => Choice::Linkedin,
+            "twitch" => Choice::Twitch,
+            "slack" => Choice::Slack,
+            "steam" => Choice::Steam,
+            "telegram" => Choice::Telegram,
+            "mastodon" => Choice::Mastodon,
+            "atlassian" => Choice::Atlassian,
+            _ => Choice::Custom(name.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Oauth2ServerResponse {
     pub access_token: PAccessToken,
     pub refresh_token: Option<PRefreshToken>,
+    /// The token secret of the OAuth 1.0a strategies, used along with the access token to sign requests.
+    pub token_secret: Option<PTokenSecret>,
     pub profile: Value,
+    /// The application roles of the user, mapped from the profile by the [`RoleMapping`] of the strategy.
+    pub roles: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Verifier(PkceCodeVerifier);
+/// How long a login is kept in progress, in seconds: the time the user has to sign in with the provider.
+pub(crate) const LOGIN_MAX_AGE: u64 = 600;
 
-#[derive(Clone)]
-pub struct Passport {
+/// How many logins are kept in progress at most by default.
+pub(crate) const MAX_LOGINS: usize = 10_000;
+
+/// A login in progress, kept until the provider redirects the user back.
+struct Pending {
+    /// The secret returned by [`Strategy::authorize`], e.g. the `PkceCodeVerifier`.
+    secret: String,
+    /// Where to send the user once authenticated, instead of the success redirect.
+    return_to: Option<Url>,
+    /// When the login started, in seconds since the epoch.
+    created: u64,
+}
+
+#[derive(Default)]
+struct Logins {
+    /// The logins in progress, by the secret of their [`CsrfToken`].
+    pending: HashMap<String, Pending>,
+    /// The states of the logins, the oldest first.
+    order: VecDeque<String>,
+}
+
+/// The outcome of a successful callback.
+struct Authenticated<U> {
+    response: Oauth2ServerResponse,
+    user: Option<U>,
+    return_to: Option<Url>,
+}
+
+/// A verify callback, see [`Passport::verify`].
+type Verify<U> = Arc<
+    dyn Fn(Oauth2ServerResponse) -> Pin<Box<dyn Future<Output = Result<U, String>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Holds the strategies and the logins in progress.
+///
+/// `U` is the application user returned by the [`Passport::verify`] callbacks. The default `Passport` has
+/// callbacks returning `()`, which only accept or reject the user. Create a `Passport::<User>::new()` for
+/// callbacks returning your `User`, then retrieve it with [`Passport::authenticate_user`].
+pub struct Passport<U = ()> {
     strategies: HashMap<Choice, Arc<dyn Strategy>>,
     /// This stores each [`BasicClient`] associated with each [`Strategy`] which will be used to communicate
     /// with the respected provider oauth2 server.
     clients: HashMap<Choice, BasicClient>,
-    /// [`CsrfToken`] and [`PkceCodeVerifier`]. We need to keep a track of the two which will be used
-    /// in getting the [`AccessToken`] from the provider.
-    /// Thereafter, be deleted from the storage since will no longer be needed.
-    sessions: HashMap<String, String>,
-    success_redirect: Option<Url>,
-    failure_redirect: Option<Url>,
+    /// [`CsrfToken`] and the secret returned by [`Strategy::authorize`] (e.g. the `PkceCodeVerifier`), along with
+    /// the validated `return_to`. We need to keep a track of the two which will be used in getting the
+    /// `AccessToken` from the provider. Thereafter, be deleted from the storage since will no longer be needed.
+    /// Shared among the clones, and only locked to insert or take a login, never across a request.
+    sessions: Arc<Mutex<Logins>>,
+    /// How many logins are kept in progress at most.
+    capacity: usize,
+    redirects: Option<Redirect>,
+    /// The redirects overriding the global ones, per strategy.
+    strategy_redirects: HashMap<Choice, Redirect>,
+    /// The HTTP client handed to [`Strategy::enrich`].
+    http: reqwest::Client,
+    /// The policies the user must satisfy, per strategy.
+    policies: HashMap<Choice, Vec<Arc<dyn Policy>>>,
+    /// The verify callbacks, per strategy.
+    verifiers: HashMap<Choice, Verify<U>>,
+    /// The role mappings, per strategy.
+    roles: HashMap<Choice, RoleMapping>,
 }
 
 impl Default for Passport {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U> Clone for Passport<U> {
+    fn clone(&self) -> Self {
         Self {
-            strategies: HashMap::new(),
-            clients: HashMap::new(),
-            sessions: HashMap::new(),
-            success_redirect: None,
-            failure_redirect: None,
+            strategies: self.strategies.clone(),
+            clients: self.clients.clone(),
+            sessions: self.sessions.clone(),
+            capacity: self.capacity,
+            redirects: self.redirects.clone(),
+            strategy_redirects: self.strategy_redirects.clone(),
+            http: self.http.clone(),
+            policies: self.policies.clone(),
+            verifiers: self.verifiers.clone(),
+            roles: self.roles.clone(),
         }
     }
 }
 
-unsafe impl Send for Passport {}
-unsafe impl Sync for Passport {}
-
 impl Passport {
-    const USER_AGENT: &'static str =
+    pub(crate) const USER_AGENT: &'static str =
         "passport-strategies/1.0 (+https://crates.io/crates/passport-strategies)";
 
+    /// Completes the authentication, returning the tokens and the profile along with the url to redirect the user
+    /// to. A [`Passport::verify`] callback of the strategy still decides whether the authentication succeeds.
+    pub async fn authenticate(
+        &self,
+        choice: Choice,
+        statecode: StateCode,
+    ) -> (Option<Oauth2ServerResponse>, String) {
+        let redirects = self.redirects(&choice);
+
+        match self.profile(choice, statecode).await {
+            Ok(authenticated) => {
+                info!("oauth2 authentication completed with no errors");
+
+                let success_redirect = authenticated
+                    .return_to
+                    .unwrap_or(redirects.success_redirect);
+                (Some(authenticated.response), success_redirect.to_string())
+            }
+
+            Err(error) => {
+                warn!(?error);
+
+                (None, redirects.failure(&error).to_string())
+            }
+        }
+    }
+}
+
+impl<U> Passport<U>
+where
+    U: Send + 'static,
+{
+    /// A passport whose [`Passport::verify`] callbacks return the application user `U`, e.g.
+    /// `Passport::<User>::new()`.
+    pub fn new() -> Self {
+        Self {
+            strategies: HashMap::new(),
+            clients: HashMap::new(),
+            sessions: Arc::new(Mutex::new(Logins::default())),
+            capacity: MAX_LOGINS,
+            redirects: None,
+            strategy_redirects: HashMap::new(),
+            http: reqwest::Client::default(),
+            policies: HashMap::new(),
+            verifiers: HashMap::new(),
+            roles: HashMap::new(),
+        }
+    }
+
     pub fn redirect_urls(mut self, redirects: Redirect) -> Self {
-        self.success_redirect = Some(redirects.success_redirect);
-        self.failure_redirect = Some(redirects.failure_redirect);
+        self.redirects = Some(redirects);
+
+        self
+    }
+
+    /// Sets the redirects of the strategy registered with `choice`, overriding the ones set with
+    /// [`Passport::redirect_urls`], e.g. to land the users of an admin console elsewhere than the customers.
+    pub fn strategy_redirect_urls(mut self, choice: Choice, redirects: Redirect) -> Self {
+        self.strategy_redirects.insert(choice, redirects);
 
         self
     }
 
-    fn redirects(&self) -> Redirect {
-        Redirect {
-            failure_redirect: self.failure_redirect.as_ref().unwrap().clone(),
-            success_redirect: self.success_redirect.as_ref().unwrap().clone(),
+    /// Sets how many logins are kept in progress at most, 10000 by default. The oldest one is dropped to make
+    /// room, and fails with [`Error::CSRFTokenMismatch`] once the user is redirected back. A login is also dropped
+    /// after 10 minutes.
+    pub fn max_logins(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Attaches a `policy` to the strategy registered with `choice`. The policies are evaluated once the profile
+    /// is retrieved, and the first one the user does not satisfy fails the authentication with
+    /// [`Error::PolicyRejected`].
+    pub fn policy<P>(mut self, choice: Choice, policy: P) -> Self
+    where
+        P: Policy + 'static,
+    {
+        self.policies
+            .entry(choice)
+            .or_default()
+            .push(Arc::new(policy));
+        self
+    }
+
+    /// Sets the [`RoleMapping`] of the strategy registered with `choice`, filling [`Oauth2ServerResponse::roles`].
+    pub fn roles(mut self, choice: Choice, mapping: RoleMapping) -> Self {
+        self.roles.insert(choice, mapping);
+        self
+    }
+
+    /// Registers the verify callback of the strategy registered with `choice`, like the one of passport.js.
+    /// It receives the tokens and the profile once the policies are satisfied, and returns the application user
+    /// (e.g. after a lookup-or-create) or the reason the user is rejected, which fails the authentication with
+    /// [`Error::VerifyRejected`]. The user is returned by [`Passport::authenticate_user`], and all the callbacks
+    /// return the `U` of the passport, so a mismatch fails to compile.
+    ///
+    /// ```rust,ignore
+    /// let passport = Passport::<User>::new()
+    ///     .strategize(Choice::Github, strategy)?
+    ///     .verify(Choice::Github, move |response: Oauth2ServerResponse| {
+    ///         let db = db.clone();
+    ///         async move {
+    ///             let user = db.find_or_create(&response.profile).await.map_err(|e| e.to_string())?;
+    ///             if user.banned {
+    ///                 return Err(String::from("banned"));
+    ///             }
+    ///             Ok(user)
+    ///         }
+    ///     });
+    ///
+    /// let (response, redirect_url) = passport.authenticate_user(Choice::Github, statecode).await;
+    /// ```
+    pub fn verify<F, Fut>(mut self, choice: Choice, verify: F) -> Self
+    where
+        F: Fn(Oauth2ServerResponse) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<U, String>> + Send + 'static,
+    {
+        let verify: Verify<U> = Arc::new(move |response| Box::pin(verify(response)));
+        self.verifiers.insert(choice, verify);
+        self
+    }
+
+    /// The strategy registered with `choice`, along with its client.
+    fn strategy(&self, choice: &Choice) -> Result<(Arc<dyn Strategy>, BasicClient), Error> {
+        match (self.strategies.get(choice), self.clients.get(choice)) {
+            (Some(strategy), Some(client)) => Ok((strategy.clone(), client.clone())),
+            _ => Err(Error::MissingStrategy),
+        }
+    }
+
+    fn redirects(&self, choice: &Choice) -> Redirect {
+        self.strategy_redirects
+            .get(choice)
+            .or(self.redirects.as_ref())
+            .unwrap()
+            .clone()
+    }
+
+    /// Keeps the login in progress, along with the `return_to` the allowlist admits.
+    fn pending(&self, choice: &Choice, state: &CsrfToken, secret: String, return_to: Option<&str>) {
+        let return_to = return_to.and_then(|return_to| {
+            let url = self.redirects(choice).return_to(return_to);
+            if url.is_none() {
+                warn!(return_to, "return_to is not allowed");
+            }
+            url
+        });
+
+        let state = state.secret().to_string();
+        let now = now();
+
+        let mut logins = self.sessions.lock().unwrap();
+        let logins = &mut *logins;
+        logins.pending.insert(
+            state.clone(),
+            Pending {
+                secret,
+                return_to,
+                created: now,
+            },
+        );
+        logins.order.push_back(state);
+
+        // The states are in the order of expiry, the completed logins are skipped.
+        while let Some(oldest) = logins.order.front() {
+            let expired = match logins.pending.get(oldest) {
+                Some(pending) => pending.created + LOGIN_MAX_AGE <= now,
+                None => true,
+            };
+            if !expired && logins.pending.len() <= self.capacity {
+                break;
+            }
+            if let Some(oldest) = logins.order.pop_front() {
+                logins.pending.remove(&oldest);
+            }
         }
     }
 
+    /// Takes the login in progress with `state`, unless it has expired.
+    fn take(&self, state: &str) -> Option<Pending> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .pending
+            .remove(state)
+            .filter(|pending| pending.created + LOGIN_MAX_AGE > now())
+    }
+
     pub fn strategize<T>(mut self, current: Choice, strategy: T) -> Result<Self, Error>
     where
         T: Strategy + Sync + Send + 'static,
@@ -126,13 +504,20 @@ impl Passport {
             Err(err) => return Err(Error::ParseError(err)),
         };
 
+        // A public client, e.g. one registered with the `none` token endpoint auth method, has no secret and only
+        // sends its client_id to the token endpoint.
+        let client_secret = Some(strategy.client_secret())
+            .filter(|secret| !secret.is_empty())
+            .map(ClientSecret::new);
+
         let client = BasicClient::new(
             ClientId::new(strategy.client_id().to_string()),
-            Some(ClientSecret::new(strategy.client_secret().to_string())),
+            client_secret,
             auth,
             Some(strategy.token_url()?),
         )
-        .set_redirect_uri(redirect_url);
+        .set_redirect_uri(redirect_url)
+        .set_auth_type(strategy.auth_type());
 
         self.clients.insert(current.clone(), client);
         self.strategies.insert(current.clone(), Arc::new(strategy));
@@ -140,59 +525,167 @@ impl Passport {
         Ok(self)
     }
 
-    pub fn redirect_url(&mut self, choice: Choice) -> String {
-        let (pkce_challenge, verifier) = PkceCodeChallenge::new_random_sha256();
-        let strategy = self.strategies.get(&choice).unwrap();
-        let scopes = strategy.scopes();
-        let client = self.clients.get(&choice).unwrap();
-        let (auth_url, csrf_token) = client
-            .authorize_url(CsrfToken::new_random)
-            .set_pkce_challenge(pkce_challenge)
-            .add_scopes(scopes)
-            .url();
+    /// Whether a strategy is registered with `choice`.
+    pub fn contains(&self, choice: &Choice) -> bool {
+        self.strategies.contains_key(choice)
+    }
+
+    /// Returns the url to redirect the user to. Once authenticated, the user is sent to `return_to` (e.g. the page
+    /// they were on) rather than to the success redirect, when the allowlist set with
+    /// [`Redirect::set_return_to_allowlist`] admits it. The stateless strategies ignore `return_to`.
+    ///
+    /// Fails with [`Error::AsyncAuthorizationRequired`] for the strategies that need
+    /// [`Passport::redirect_url_async`], e.g. Mastodon and OAuth 1.0a.
+    pub fn redirect_url(&self, choice: Choice, return_to: Option<&str>) -> Result<String, Error> {
+        let (strategy, client) = self.strategy(&choice)?;
+        let (auth_url, csrf_token, secret) = strategy.authorize(&client)?;
+
+        if strategy.stateless() {
+            return Ok(auth_url.to_string());
+        }
 
-        // We need to keep track of the `PkceVerifier` since it will be needed later to verify
+        // We need to keep track of the secret (e.g. the `PkceVerifier`) since it will be needed later to verify
         // the `Authorization Code` later sent from the provider server.
-        // Tricky, `PkceVerifier` neither implements `Copy` nor `Clone`
-        // but only implements `Serialize` and `Deserialize`. So, serializing it to a String then
-        // storing it will enable us to work with its clone in
-        // `Passport::profile` when setting a pkce verifier for the `Authorization Code`. This way, the compiler won't complain.
-        let data = Verifier(verifier);
-        let json_value = serde_json::to_string(&data).unwrap();
+        self.pending(&choice, &csrf_token, secret, return_to);
 
-        self.sessions
-            .insert(csrf_token.secret().to_string(), json_value);
+        Ok(auth_url.to_string())
+    }
 
-        auth_url.to_string()
+    /// Like [`Passport::redirect_url`], for strategies that need to talk to the provider before redirecting
+    /// the user, e.g. to register the app on the user's Mastodon `instance`.
+    pub async fn redirect_url_async(
+        &self,
+        choice: Choice,
+        instance: Option<&str>,
+        return_to: Option<&str>,
+    ) -> Result<String, Error> {
+        self.authorize(choice, instance, return_to)
+            .await
+            .map(|(auth_url, _)| auth_url)
     }
 
-    pub async fn authenticate(
-        &mut self,
+    /// Returns the url of [`Passport::redirect_url_async`] along with its state, empty for the stateless
+    /// strategies, for the integrations to bind it to the browser.
+    pub(crate) async fn authorize(
+        &self,
+        choice: Choice,
+        instance: Option<&str>,
+        return_to: Option<&str>,
+    ) -> Result<(String, String), Error> {
+        let (strategy, client) = self.strategy(&choice)?;
+        let (auth_url, csrf_token, secret) = strategy.authorize_async(&client, instance).await?;
+
+        if strategy.stateless() {
+            return Ok((auth_url.to_string(), String::new()));
+        }
+
+        self.pending(&choice, &csrf_token, secret, return_to);
+
+        Ok((auth_url.to_string(), csrf_token.secret().to_string()))
+    }
+
+    /// The failure redirect for `error`, for the integrations to reject a request before it reaches the strategy.
+    #[cfg(any(feature = "tower", feature = "actix"))]
+    pub(crate) fn failure_redirect(&self, choice: &Choice, error: &Error) -> String {
+        self.redirects(choice).failure(error).to_string()
+    }
+
+    /// Like [`Passport::authenticate`], also returning the user from the [`Passport::verify`] callback of the
+    /// strategy. Fails with [`Error::MissingUser`] when the strategy has no verify callback.
+    pub async fn authenticate_user(
+        &self,
         choice: Choice,
         statecode: StateCode,
-    ) -> (Option<Oauth2ServerResponse>, String) {
-        let redirects = self.redirects();
+    ) -> (Option<(Oauth2ServerResponse, U)>, String) {
+        let redirects = self.redirects(&choice);
 
-        match self.profile(choice, statecode).await {
-            Ok(value) => {
+        let result = self
+            .profile(choice, statecode)
+            .await
+            .and_then(|authenticated| match authenticated.user {
+                Some(user) => Ok((authenticated.response, user, authenticated.return_to)),
+                None => Err(Error::MissingUser),
+            });
+
+        match result {
+            Ok((value, user, return_to)) => {
                 info!("oauth2 authentication completed with no errors");
 
-                (Some(value), redirects.success_redirect.to_string())
+                let success_redirect = return_to.unwrap_or(redirects.success_redirect);
+                (Some((value, user)), success_redirect.to_string())
             }
 
             Err(error) => {
                 warn!(?error);
 
-                (None, redirects.failure_redirect.to_string())
+                (None, redirects.failure(&error).to_string())
             }
         }
     }
 
+    /// Exchanges the `refresh_token` for a new access token. Providers that rotate refresh tokens
+    /// return the new refresh token in the [`TokenSet`], make sure to replace the old one.
+    pub async fn refresh(
+        &self,
+        choice: Choice,
+        refresh_token: &PRefreshToken,
+    ) -> Result<TokenSet, Error> {
+        let (strategy, client) = self.strategy(&choice)?;
+
+        strategy.refresh(&client, refresh_token).await
+    }
+
     async fn profile(
-        &mut self,
+        &self,
         choice: Choice,
         statecode: StateCode,
-    ) -> Result<Oauth2ServerResponse, Error> {
+    ) -> Result<Authenticated<U>, Error> {
+        let (strategy, _) = self.strategy(&choice)?;
+        let (mut response, return_to) = self.callback(&choice, statecode).await?;
+
+        strategy
+            .enrich(&self.http, &response.access_token, &mut response.profile)
+            .await?;
+
+        if let Some(mapping) = self.roles.get(&choice) {
+            response.roles = mapping.roles(&response.profile);
+        }
+
+        for policy in self.policies.get(&choice).into_iter().flatten() {
+            policy.evaluate(&response).map_err(Error::PolicyRejected)?;
+        }
+
+        let user = match self.verifiers.get(&choice) {
+            Some(verify) => Some(
+                verify(response.clone())
+                    .await
+                    .map_err(Error::VerifyRejected)?,
+            ),
+            None => None,
+        };
+
+        Ok(Authenticated {
+            response,
+            user,
+            return_to,
+        })
+    }
+
+    async fn callback(
+        &self,
+        choice: &Choice,
+        statecode: StateCode,
+    ) -> Result<(Oauth2ServerResponse, Option<Url>), Error> {
+        let (strategy, client) = self.strategy(choice)?;
+
+        if strategy.stateless() {
+            let response = strategy
+                .callback(&client, String::new(), &statecode)
+                .await?;
+
+            return Ok((response, None));
+        }
+
         if statecode.state.is_none() && statecode.code.is_none() {
             return Err(Error::MissingAuthorizationCodeAndCsrfToken);
         }
@@ -201,131 +694,376 @@ impl Passport {
             return Err(Error::MissingCsrfToken);
         }
 
-        if statecode.code.is_none() {
-            return Err(Error::MissingAuthorizationCode);
+        // Clearing the nolonger needed secret from the memmory
+        match self.take(statecode.state.as_ref().unwrap().secret()) {
+            Some(pending) => {
+                let response = strategy
+                    .callback(&client, pending.secret, &statecode)
+                    .await?;
+
+                Ok((response, pending.return_to))
+            }
+            None => Err(Error::CSRFTokenMismatch),
         }
+    }
+}
 
-        let bind = &self.strategies;
-        let strategy = bind.get(&choice).unwrap();
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use oauth2::TokenUrl;
+    use serde_json::json;
 
-        match self
-            .sessions
-            .get(statecode.state.as_ref().unwrap().secret())
-        {
-            Some(verifier) => {
-                let json_pkce: Verifier = serde_json::from_str(&verifier).unwrap();
-                let clients = self.clients.get(&choice).unwrap();
-
-                if let Choice::Reddit = choice {
-                    let response = reqwest::Client::new()
-                        .post(strategy.token_url()?.to_string())
-                        .basic_auth(strategy.client_id(), Some(strategy.client_secret()))
-                        .form(&[
-                            ("grant_type", "authorization_code"),
-                            ("code", statecode.code.clone().unwrap().as_str()),
-                            ("redirect_uri", &strategy.redirect_url()),
-                            ("code_verifier", &verifier),
-                        ])
-                        .header(reqwest::header::USER_AGENT, Self::USER_AGENT)
-                        .send()
-                        .await
-                        .map_err(|e| Error::Reqwest(e.to_string()))
-                        .map(|v| v)?;
-                    
-                    self.sessions.remove(statecode.state.unwrap().secret()); // Clearing the nolonger needed verifier from the memmory
-                    if response.status().is_success() {
-                        response
-                            .json::<serde_json::Value>()
-                            .await
-                            .map_err(|error| Error::Reqwest(error.to_string()))
-                            .map(|mut profile| {
-                                let mut refresh_token = None;
-
-                                let access_token =
-                                    PAccessToken(profile["access_token"].to_string());
-                                
-                                let _ = profile.as_object_mut().and_then(|map| map.remove("access_token"));
-
-                                if profile["refresh_token"].is_string() {
-                                    refresh_token =
-                                        Some(PRefreshToken(profile["refresh_token"].to_string()));
-                                    
-                                };
-                                
-                                let _ = profile.as_object_mut().and_then(|map| map.remove("refresh_token"));
-
-                                return Ok(Oauth2ServerResponse {
-                                    access_token,
-                                    refresh_token,
-                                    profile,
-                                });
-                            })?
-                    } else {
-                        return Err(Error::Reqwest(response.text().await.unwrap()));
-                    }
-                } else {
-                    match clients
-                        .exchange_code(AuthorizationCode::new(statecode.code.clone().unwrap()))
-                        .set_pkce_verifier(json_pkce.0)
-                        .request_async(async_http_client)
-                        .await
-                    {
-                        Ok(token) => {
-                            self.sessions.remove(statecode.state.unwrap().secret()); // Clearing the nolonger needed verifier from the memmory
-
-                            match reqwest::Client::new()
-                                .get(strategy.request_uri())
-                                .header(
-                                    reqwest::header::AUTHORIZATION,
-                                    format!("Bearer {}", token.access_token().secret()),
-                                )
-                                .header(reqwest::header::USER_AGENT, Self::USER_AGENT)
-                                .send()
-                                .await
-                            {
-                                Ok(response) => {
-                                    if response.status().is_success() {
-                                        response
-                                            .json::<serde_json::Value>()
-                                            .await
-                                            .map_err(|error| Error::Reqwest(error.to_string()))
-                                            .map(|mut profile| {
-                                                let mut refresh_token = None;
-
-                                                let access_token = PAccessToken(
-                                                    token.access_token().secret().into(),
-                                                );
-
-                                                let _ = profile.as_object_mut().and_then(|map| map.remove("access_token"));
-
-                                                if let Some(re) = token.refresh_token() {
-                                                    refresh_token =
-                                                        Some(PRefreshToken(re.secret().to_owned()));
-                                                };
-
-                                                let _ = profile.as_object_mut().and_then(|map| map.remove("refresh_token"));
-
-                                                return Ok(Oauth2ServerResponse {
-                                                    access_token,
-                                                    refresh_token,
-                                                    profile,
-                                                });
-                                            })?
-                                    } else {
-                                        Err(Error::Reqwest(response.text().await.unwrap()))
-                                    }
-                                }
-                                Err(e) => Err(Error::Reqwest(e.to_string())),
-                            }
-                        }
-                        Err(err) => {
-                            self.sessions.remove(statecode.state.unwrap().secret()); // Clearing the nolonger needed verifier from the memmory
-                            Err(Error::Reqwest(err.to_string()))
-                        }
-                    }
-                }
+    use super::*;
+    use crate::strategies::GithubStrategy;
+
+    /// A stateless strategy, authenticating whoever reaches the redirect url unless it fails with `error`.
+    #[derive(Default)]
+    struct Stateless {
+        error: Option<fn() -> Error>,
+    }
+
+    #[async_trait]
+    impl Strategy for Stateless {
+        fn redirect_url(&self) -> String {
+            String::from("https://ourco.com/auth/callback")
+        }
+        fn request_uri(&self) -> String {
+            String::new()
+        }
+        fn scopes(&self) -> Vec<oauth2::Scope> {
+            Vec::new()
+        }
+        fn client_id(&self) -> String {
+            String::from("id")
+        }
+        fn client_secret(&self) -> String {
+            String::new()
+        }
+        fn auth_url(&self) -> String {
+            String::from("https://provider.com/authorize")
+        }
+        fn token_url(&self) -> Result<TokenUrl, Error> {
+            Ok(TokenUrl::new(String::from("https://provider.com/token"))?)
+        }
+        fn stateless(&self) -> bool {
+            true
+        }
+        async fn callback(
+            &self,
+            _client: &BasicClient,
+            _secret: String,
+            _statecode: &StateCode,
+        ) -> Result<Oauth2ServerResponse, Error> {
+            if let Some(error) = self.error {
+                return Err(error());
             }
-            None => Err(Error::CSRFTokenMismatch),
+
+            Ok(Oauth2ServerResponse {
+                access_token: PAccessToken(String::new()),
+                refresh_token: None,
+                token_secret: None,
+                profile: json!({ "id": "ada" }),
+                roles: Vec::new(),
+            })
+        }
+    }
+
+    fn statecode() -> StateCode {
+        StateCode {
+            state: None,
+            code: None,
+            user: None,
+            params: HashMap::new(),
+        }
+    }
+
+    fn redirects() -> Redirect {
+        Redirect::new("https://ourco.com/login", "https://ourco.com/").unwrap()
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    fn github() -> Passport {
+        Passport::default()
+            .redirect_urls(redirects().set_failure_reason(true))
+            .strategize(
+                Choice::Github,
+                GithubStrategy::new(
+                    "id",
+                    "secret",
+                    &[],
+                    "https://ourco.com/auth/github/callback",
+                ),
+            )
+            .unwrap()
+    }
+
+    /// The statecode the provider redirects back with, for the state of the authorization `url`.
+    fn state(url: &str) -> StateCode {
+        let state = Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .find(|(name, _)| name == "state")
+            .map(|(_, state)| CsrfToken::new(state.into_owned()));
+
+        StateCode {
+            state,
+            code: Some(String::from("code")),
+            ..statecode()
+        }
+    }
+
+    fn is_pending(passport: &Passport, url: &str) -> bool {
+        let state = state(url).state.unwrap();
+        passport
+            .sessions
+            .lock()
+            .unwrap()
+            .pending
+            .contains_key(state.secret())
+    }
+
+    #[tokio::test]
+    async fn returns_the_verified_user() {
+        let passport = Passport::<User>::new()
+            .redirect_urls(redirects())
+            .strategize(Choice::Steam, Stateless::default())
+            .unwrap()
+            .verify(Choice::Steam, |response: Oauth2ServerResponse| async move {
+                Ok(User(response.profile["id"].as_str().unwrap().to_string()))
+            });
+
+        let (user, redirect_url) = passport.authenticate_user(Choice::Steam, statecode()).await;
+        assert_eq!(user.map(|(_, user)| user), Some(User(String::from("ada"))));
+        assert_eq!(redirect_url, "https://ourco.com/");
+    }
+
+    #[tokio::test]
+    async fn rejects_without_a_verify_callback() {
+        let passport = Passport::<User>::new()
+            .redirect_urls(redirects())
+            .strategize(Choice::Steam, Stateless::default())
+            .unwrap();
+
+        let (user, redirect_url) = passport.authenticate_user(Choice::Steam, statecode()).await;
+        assert!(user.is_none());
+        assert_eq!(redirect_url, "https://ourco.com/login");
+    }
+
+    #[tokio::test]
+    async fn verifies_with_the_default_passport() {
+        let passport = Passport::default()
+            .redirect_urls(redirects())
+            .strategize(Choice::Steam, Stateless::default())
+            .unwrap()
+            .verify(Choice::Steam, |_| async { Err(String::from("banned")) });
+
+        let (response, redirect_url) = passport.authenticate(Choice::Steam, statecode()).await;
+        assert!(response.is_none());
+        assert_eq!(redirect_url, "https://ourco.com/login");
+    }
+
+    #[test]
+    fn admits_the_allowed_return_to() {
+        let redirects = redirects().set_return_to_allowlist(&["/app", "https://docs.ourco.com"]);
+        let return_to = |return_to: &str| redirects.return_to(return_to).map(String::from);
+
+        assert_eq!(
+            return_to("/app/settings?tab=1").as_deref(),
+            Some("https://ourco.com/app/settings?tab=1")
+        );
+        assert_eq!(return_to("/app").as_deref(), Some("https://ourco.com/app"));
+        // An origin entry admits any path of the origin.
+        assert_eq!(
+            return_to("https://docs.ourco.com/guide").as_deref(),
+            Some("https://docs.ourco.com/guide")
+        );
+    }
+
+    #[test]
+    fn rejects_the_open_redirects() {
+        let redirects = redirects().set_return_to_allowlist(&["/app", "https://docs.ourco.com"]);
+
+        for return_to in [
+            "//evil.com",
+            "//evil.com/app",
+            "/\\evil.com",
+            "/\\evil.com/app",
+            "https://evil.com",
+            "https://evil.com/app",
+            "https://docs.ourco.com.evil.com",
+            "http://docs.ourco.com",
+            "javascript:alert(1)",
+            "JavaScript://ourco.com/app/%0aalert(1)",
+            "/app/../admin",
+            "/app/%2e%2e/admin",
+            "/app/%2E%2E/admin",
+            "/application",
+            "/admin",
+        ] {
+            assert_eq!(redirects.return_to(return_to), None, "{return_to}");
+        }
+    }
+
+    #[test]
+    fn rejects_any_return_to_without_an_allowlist() {
+        assert_eq!(redirects().return_to("/app"), None);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_strategies() {
+        let passport = Passport::default()
+            .redirect_urls(redirects().set_failure_reason(true))
+            .strategize(Choice::Steam, Stateless::default())
+            .unwrap();
+
+        assert!(matches!(
+            passport.redirect_url(Choice::Github, None),
+            Err(Error::MissingStrategy)
+        ));
+        assert!(matches!(
+            passport.authorize(Choice::Github, None, None).await,
+            Err(Error::MissingStrategy)
+        ));
+        assert!(matches!(
+            passport
+                .refresh(Choice::Github, &PRefreshToken(String::from("token")))
+                .await,
+            Err(Error::MissingStrategy)
+        ));
+
+        let (response, redirect_url) = passport.authenticate(Choice::Github, statecode()).await;
+        assert!(response.is_none());
+        assert_eq!(
+            redirect_url,
+            "https://ourco.com/login?error=invalid_request"
+        );
+    }
+
+    fn failing(error: fn() -> Error) -> Stateless {
+        Stateless { error: Some(error) }
+    }
+
+    #[tokio::test]
+    async fn prefers_the_strategy_redirects() {
+        let admin =
+            Redirect::new("https://admin.ourco.com/login", "https://admin.ourco.com/").unwrap();
+        let passport = Passport::default()
+            .redirect_urls(redirects())
+            .strategy_redirect_urls(Choice::Steam, admin.clone())
+            .strategy_redirect_urls(Choice::Custom(String::from("denied")), admin)
+            .strategize(Choice::Steam, Stateless::default())
+            .unwrap()
+            .strategize(
+                Choice::Custom(String::from("denied")),
+                failing(|| Error::MissingAuthorizationCode),
+            )
+            .unwrap()
+            .strategize(Choice::Telegram, Stateless::default())
+            .unwrap()
+            .strategize(Choice::Twitter, failing(|| Error::MissingAuthorizationCode))
+            .unwrap();
+
+        for (choice, expected) in [
+            (Choice::Steam, "https://admin.ourco.com/"),
+            (
+                Choice::Custom(String::from("denied")),
+                "https://admin.ourco.com/login",
+            ),
+            // The strategies without redirects of their own fall back to the global ones.
+            (Choice::Telegram, "https://ourco.com/"),
+            (Choice::Twitter, "https://ourco.com/login"),
+        ] {
+            let (_, redirect_url) = passport.authenticate(choice, statecode()).await;
+            assert_eq!(redirect_url, expected);
         }
     }
+
+    #[tokio::test]
+    async fn appends_the_failure_reason() {
+        let passport = Passport::default()
+            .redirect_urls(redirects().set_failure_reason(true))
+            .strategize(Choice::Steam, failing(|| Error::MissingAuthorizationCode))
+            .unwrap()
+            .strategize(
+                Choice::Discord,
+                failing(|| Error::DiscordNotMember(String::from("ada"))),
+            )
+            .unwrap()
+            .strategize(Choice::Telegram, Stateless::default())
+            .unwrap()
+            .policy(Choice::Telegram, crate::policy::VerifiedEmail);
+
+        for (choice, reason) in [
+            (Choice::Steam, "access_denied"),
+            (Choice::Discord, "not_member"),
+            (Choice::Telegram, "not_allowed"),
+        ] {
+            let (response, redirect_url) = passport.authenticate(choice, statecode()).await;
+            assert!(response.is_none());
+            assert_eq!(
+                redirect_url,
+                format!("https://ourco.com/login?error={reason}")
+            );
+        }
+
+        // The reason is left out unless asked for.
+        let passport = Passport::default()
+            .redirect_urls(redirects())
+            .strategize(Choice::Steam, failing(|| Error::MissingAuthorizationCode))
+            .unwrap();
+        let (_, redirect_url) = passport.authenticate(Choice::Steam, statecode()).await;
+        assert_eq!(redirect_url, "https://ourco.com/login");
+    }
+
+    #[tokio::test]
+    async fn drops_the_oldest_login() {
+        let passport = github().max_logins(1);
+        let first = passport.redirect_url(Choice::Github, None).unwrap();
+        let second = passport.redirect_url(Choice::Github, None).unwrap();
+
+        assert!(!is_pending(&passport, &first));
+        assert!(is_pending(&passport, &second));
+
+        let (response, redirect_url) = passport.authenticate(Choice::Github, state(&first)).await;
+        assert!(response.is_none());
+        assert_eq!(redirect_url, "https://ourco.com/login?error=invalid_state");
+    }
+
+    #[tokio::test]
+    async fn expires_the_logins() {
+        let passport = github();
+        let first = passport.redirect_url(Choice::Github, None).unwrap();
+        let second = passport.redirect_url(Choice::Github, None).unwrap();
+        for pending in passport.sessions.lock().unwrap().pending.values_mut() {
+            pending.created -= LOGIN_MAX_AGE;
+        }
+
+        let (response, redirect_url) = passport.authenticate(Choice::Github, state(&first)).await;
+        assert!(response.is_none());
+        assert_eq!(redirect_url, "https://ourco.com/login?error=invalid_state");
+
+        // The expired logins are dropped as the next one starts.
+        let third = passport.redirect_url(Choice::Github, None).unwrap();
+        assert!(!is_pending(&passport, &second));
+        assert!(is_pending(&passport, &third));
+        assert_eq!(passport.sessions.lock().unwrap().pending.len(), 1);
+    }
+
+    #[test]
+    fn shares_the_logins_among_the_clones() {
+        let passport = github();
+        let url = passport.clone().redirect_url(Choice::Github, None).unwrap();
+
+        assert!(is_pending(&passport, &url));
+    }
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Passport>();
+        assert_send_sync::<Passport<User>>();
+    }
 }
@@ -0,0 +1,473 @@
+use serde_json::Value;
+
+use crate::passport::Oauth2ServerResponse;
+
+/// A rule the authenticated user must satisfy, evaluated by the [`Passport`](crate::passport::Passport) once the
+/// profile is retrieved and before the success redirect. Attach it with
+/// [`Passport::policy`](crate::passport::Passport::policy).
+///
+/// The claims of the `id_token` are in the profile under `id_token_claims` when the `openid` scope is requested,
+/// e.g. the Microsoft tenant `tid` or the Google Workspace domain `hd`, which the Graph and People APIs don't
+/// return:
+///
+/// ```rust,ignore
+/// let passport = passport
+///     .policy(Choice::Google, EmailDomain::allow(&["ourco.com"]).and(VerifiedEmail))
+///     .policy(Choice::Microsoft, Claim::is_in("/id_token_claims/tid", &[tenant_a, tenant_b]))
+///     .policy(Choice::Github, Membership::github_org("ourco").or(Membership::github_team("partner/devs")));
+/// ```
+pub trait Policy: Send + Sync {
+    /// Returns the reason the user is rejected, if any.
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String>;
+
+    /// Both this policy and `other` must be satisfied.
+    fn and<P>(self, other: P) -> All
+    where
+        Self: Sized + 'static,
+        P: Policy + 'static,
+    {
+        All(vec![Box::new(self), Box::new(other)])
+    }
+
+    /// Either this policy or `other` must be satisfied.
+    fn or<P>(self, other: P) -> Any
+    where
+        Self: Sized + 'static,
+        P: Policy + 'static,
+    {
+        Any(vec![Box::new(self), Box::new(other)])
+    }
+
+    /// This policy must not be satisfied.
+    fn not(self) -> Not
+    where
+        Self: Sized + 'static,
+    {
+        Not(Box::new(self))
+    }
+}
+
+/// Satisfied when all of the policies are.
+pub struct All(pub Vec<Box<dyn Policy>>);
+
+impl Policy for All {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        self.0
+            .iter()
+            .try_for_each(|policy| policy.evaluate(response))
+    }
+}
+
+/// Satisfied when any of the policies is.
+pub struct Any(pub Vec<Box<dyn Policy>>);
+
+impl Policy for Any {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        let mut reasons = Vec::new();
+        for policy in &self.0 {
+            match policy.evaluate(response) {
+                Ok(()) => return Ok(()),
+                Err(reason) => reasons.push(reason),
+            }
+        }
+
+        Err(reasons.join(" or "))
+    }
+}
+
+/// Satisfied when the policy is not.
+pub struct Not(pub Box<dyn Policy>);
+
+impl Policy for Not {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        match self.0.evaluate(response) {
+            Ok(()) => Err(String::from("a denied policy is satisfied")),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Looks `path` up in the profile, either a top-level key (`hd`) or a JSON pointer (`/realm_access/roles`).
+pub(crate) fn lookup<'a>(profile: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.starts_with('/') {
+        profile.pointer(path)
+    } else {
+        profile.get(path)
+    }
+}
+
+/// The email of the profile, whatever the provider calls it. The Github `/user` hides private emails, so the
+/// primary one of [`EndpointEnricher::github_emails`](crate::strategies::EndpointEnricher::github_emails) is used.
+fn email(profile: &Value) -> Option<&str> {
+    [
+        "/email",
+        "/mail",
+        "/emailAddresses/0/value",
+        "/data/email",
+        "/id_token_claims/email",
+    ]
+    .iter()
+    .find_map(|pointer| profile.pointer(pointer).and_then(Value::as_str))
+    .or_else(|| {
+        profile["emails"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|email| email["primary"] == true)
+            .and_then(|email| email["email"].as_str())
+    })
+}
+
+fn is_true(value: &Value) -> bool {
+    // Apple sends the boolean claims as strings.
+    *value == Value::Bool(true) || *value == "true"
+}
+
+/// Whether the provider verified the email of the profile. Profiles without an email are never verified.
+fn email_verified(profile: &Value) -> bool {
+    let Some(email) = email(profile) else {
+        return false;
+    };
+
+    let mut claims = vec![
+        "/email_verified",
+        "/id_token_claims/email_verified",
+        "/id_token_claims/xms_edov",
+    ];
+    // Discord flags the email with a top-level `verified`, which X (Twitter) uses for the blue check instead.
+    if profile.get("discriminator").is_some() {
+        claims.push("/verified");
+    }
+    if claims
+        .iter()
+        .filter_map(|pointer| profile.pointer(pointer))
+        .any(is_true)
+    {
+        return true;
+    }
+
+    // The Google People API and the Github emails list flag each email.
+    [
+        ("emailAddresses", "value", "/metadata/verified"),
+        ("emails", "email", "/verified"),
+    ]
+    .iter()
+    .any(|(list, key, verified)| {
+        profile[list]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry[key].as_str() == Some(email))
+            .filter_map(|entry| entry.pointer(verified))
+            .any(is_true)
+    })
+}
+
+/// Checks the domain of the user's email.
+pub struct EmailDomain {
+    domains: Vec<String>,
+    allow: bool,
+}
+
+impl EmailDomain {
+    /// Only admits emails of the `domains`.
+    pub fn allow(domains: &[&str]) -> Self {
+        Self {
+            domains: domains.iter().map(|domain| domain.to_lowercase()).collect(),
+            allow: true,
+        }
+    }
+
+    /// Rejects emails of the `domains`.
+    pub fn block(domains: &[&str]) -> Self {
+        Self {
+            domains: domains.iter().map(|domain| domain.to_lowercase()).collect(),
+            allow: false,
+        }
+    }
+}
+
+impl Policy for EmailDomain {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        let email = email(&response.profile).ok_or_else(|| String::from("email is missing"))?;
+        let domain = email
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_lowercase())
+            .unwrap_or_default();
+
+        if self.domains.contains(&domain) == self.allow {
+            Ok(())
+        } else {
+            Err(format!("email domain `{domain}` is not allowed"))
+        }
+    }
+}
+
+/// Requires the user's email to be verified by the provider:
+///
+/// * the `email_verified` claim, top-level or of the `id_token`, e.g. Google, Apple, Slack or Gitlab, or the
+///   `verified` flag of Discord.
+/// * the `metadata.verified` flag of the email in a Google People API profile.
+/// * the `verified` flag of the email in the list of [`EndpointEnricher::github_emails`](
+///   crate::strategies::EndpointEnricher::github_emails), which Github needs since `/user` has no such flag.
+/// * the `xms_edov` optional claim of a Microsoft `id_token`, which the app must be configured to send since
+///   Graph `/me` has no such flag.
+pub struct VerifiedEmail;
+
+impl Policy for VerifiedEmail {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        if email_verified(&response.profile) {
+            Ok(())
+        } else {
+            Err(String::from("email is not verified"))
+        }
+    }
+}
+
+/// Checks a claim of the profile, e.g. the Google `/id_token_claims/hd` or the Microsoft `/id_token_claims/tid`.
+pub struct Claim {
+    path: String,
+    values: Vec<Value>,
+}
+
+impl Claim {
+    /// The claim at `path` must equal `value`.
+    pub fn equals<V>(path: &str, value: V) -> Self
+    where
+        V: Into<Value>,
+    {
+        Self {
+            path: path.to_string(),
+            values: vec![value.into()],
+        }
+    }
+
+    /// The claim at `path` must be one of the `values`.
+    pub fn is_in<V>(path: &str, values: &[V]) -> Self
+    where
+        V: Into<Value> + Clone,
+    {
+        Self {
+            path: path.to_string(),
+            values: values.iter().cloned().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Policy for Claim {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        match lookup(&response.profile, &self.path) {
+            Some(value) if self.values.contains(value) => Ok(()),
+            Some(value) => Err(format!("claim `{}` = {} is not allowed", self.path, value)),
+            None => Err(format!("claim `{}` is missing", self.path)),
+        }
+    }
+}
+
+/// Checks the memberships resolved by the provider-specific gating, e.g.
+/// [`GithubStrategy::set_allowed`](crate::strategies::GithubStrategy::set_allowed) or
+/// [`DiscordStrategy::set_guild`](crate::strategies::DiscordStrategy::set_guild).
+pub struct Membership {
+    path: String,
+    key: String,
+    value: String,
+}
+
+impl Membership {
+    /// The user must be a member of the Github `org`.
+    pub fn github_org(org: &str) -> Self {
+        Self {
+            path: String::from("/memberships/orgs"),
+            key: String::from("org"),
+            value: org.to_string(),
+        }
+    }
+
+    /// The user must be a member of the Github team, given as an `org/team` slug.
+    pub fn github_team(team: &str) -> Self {
+        Self {
+            path: String::from("/memberships/teams"),
+            key: String::from("team"),
+            value: team.to_string(),
+        }
+    }
+
+    /// The Discord guild member must have the `role`.
+    pub fn discord_role(role: &str) -> Self {
+        Self {
+            path: String::from("/member/roles"),
+            key: String::new(),
+            value: role.to_string(),
+        }
+    }
+}
+
+impl Policy for Membership {
+    fn evaluate(&self, response: &Oauth2ServerResponse) -> Result<(), String> {
+        let member = response
+            .profile
+            .pointer(&self.path)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|membership| match self.key.as_str() {
+                "" => membership,
+                key => &membership[key],
+            })
+            .filter_map(Value::as_str)
+            .any(|value| value.eq_ignore_ascii_case(&self.value));
+
+        if member {
+            Ok(())
+        } else {
+            Err(format!("membership `{}` is missing", self.value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::strategies::PAccessToken;
+
+    fn response(profile: Value) -> Oauth2ServerResponse {
+        Oauth2ServerResponse {
+            access_token: PAccessToken(String::new()),
+            refresh_token: None,
+            token_secret: None,
+            profile,
+            roles: Vec::new(),
+        }
+    }
+
+    /// A People API profile, with the claims of the `id_token` of the `openid` scope.
+    fn google() -> Oauth2ServerResponse {
+        response(json!({
+            "resourceName": "people/1",
+            "emailAddresses": [{
+                "metadata": { "primary": true, "verified": true, "source": { "type": "ACCOUNT" } },
+                "value": "ada@ourco.com"
+            }],
+            "id_token_claims": { "sub": "1", "email": "ada@ourco.com", "email_verified": true, "hd": "ourco.com" }
+        }))
+    }
+
+    /// A Graph `/me` profile, with the claims of the `id_token` of the `openid` scope.
+    fn microsoft() -> Oauth2ServerResponse {
+        response(json!({
+            "id": "0b5f",
+            "displayName": "Ada",
+            "mail": "ada@ourco.com",
+            "userPrincipalName": "ada@ourco.com",
+            "id_token_claims": { "oid": "0b5f", "tid": "tenant-a" }
+        }))
+    }
+
+    /// A Github `/user` profile hiding the email, with the list of `EndpointEnricher::github_emails`.
+    fn github() -> Oauth2ServerResponse {
+        response(json!({
+            "login": "ada",
+            "id": 1,
+            "email": null,
+            "emails": [
+                { "email": "ada@users.noreply.github.com", "primary": false, "verified": true },
+                { "email": "ada@ourco.com", "primary": true, "verified": true }
+            ]
+        }))
+    }
+
+    #[test]
+    fn verifies_google_emails() {
+        assert!(VerifiedEmail.evaluate(&google()).is_ok());
+
+        let mut people = google();
+        people
+            .profile
+            .as_object_mut()
+            .unwrap()
+            .remove("id_token_claims");
+        assert!(VerifiedEmail.evaluate(&people).is_ok());
+
+        people.profile["emailAddresses"][0]["metadata"]["verified"] = json!(false);
+        assert!(VerifiedEmail.evaluate(&people).is_err());
+    }
+
+    #[test]
+    fn verifies_github_emails() {
+        assert!(VerifiedEmail.evaluate(&github()).is_ok());
+        assert!(EmailDomain::allow(&["ourco.com"])
+            .evaluate(&github())
+            .is_ok());
+
+        let mut unverified = github();
+        unverified.profile["emails"][1]["verified"] = json!(false);
+        assert!(VerifiedEmail.evaluate(&unverified).is_err());
+
+        let mut without_emails = github();
+        without_emails
+            .profile
+            .as_object_mut()
+            .unwrap()
+            .remove("emails");
+        assert!(VerifiedEmail.evaluate(&without_emails).is_err());
+    }
+
+    #[test]
+    fn verifies_microsoft_emails_with_the_optional_claim() {
+        assert!(VerifiedEmail.evaluate(&microsoft()).is_err());
+
+        let mut verified = microsoft();
+        verified.profile["id_token_claims"]["xms_edov"] = json!(true);
+        assert!(VerifiedEmail.evaluate(&verified).is_ok());
+    }
+
+    #[test]
+    fn verifies_apple_string_claims() {
+        let apple =
+            response(json!({ "sub": "1", "email": "ada@ourco.com", "email_verified": "true" }));
+
+        assert!(VerifiedEmail.evaluate(&apple).is_ok());
+    }
+
+    #[test]
+    fn verifies_discord_emails_only() {
+        let discord = response(json!({
+            "id": "80351110224678912",
+            "username": "ada",
+            "discriminator": "0",
+            "email": "ada@ourco.com",
+            "verified": true
+        }));
+        assert!(VerifiedEmail.evaluate(&discord).is_ok());
+
+        let mut unverified = discord;
+        unverified.profile["verified"] = json!(false);
+        assert!(VerifiedEmail.evaluate(&unverified).is_err());
+
+        // The blue check of X (Twitter).
+        let twitter = response(json!({ "id": "2244994945", "username": "ada", "verified": true }));
+        assert!(VerifiedEmail.evaluate(&twitter).is_err());
+
+        let mut twitter_with_email = twitter;
+        twitter_with_email.profile["email"] = json!("ada@ourco.com");
+        assert!(VerifiedEmail.evaluate(&twitter_with_email).is_err());
+
+        let claims_only = response(json!({ "sub": "1", "email_verified": true }));
+        assert!(VerifiedEmail.evaluate(&claims_only).is_err());
+    }
+
+    #[test]
+    fn checks_id_token_claims() {
+        let tenant = Claim::is_in("/id_token_claims/tid", &["tenant-a", "tenant-b"]);
+        assert!(tenant.evaluate(&microsoft()).is_ok());
+        assert!(Claim::is_in("tid", &["tenant-a"])
+            .evaluate(&microsoft())
+            .is_err());
+
+        let domain = Claim::equals("/id_token_claims/hd", "ourco.com");
+        assert!(domain.evaluate(&google()).is_ok());
+        assert!(domain.and(VerifiedEmail).evaluate(&google()).is_ok());
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::Value;
+
+use crate::policy::lookup;
+
+/// Maps the claims of the profile to application roles, attached to a strategy with
+/// [`Passport::roles`](crate::passport::Passport::roles). The roles are set on
+/// [`Oauth2ServerResponse::roles`](crate::passport::Oauth2ServerResponse::roles) before the policies and the verify
+/// callback are run.
+///
+/// Paths are either a top-level key of the profile (`groups`) or a JSON pointer (`/realm_access/roles`).
+/// The claims of the `id_token` of OpenID Connect providers are found under `/id_token_claims`.
+///
+/// ```rust
+/// use passport_strategies::roles::RoleMapping;
+/// use serde_json::json;
+///
+/// let mapping = RoleMapping::default()
+///     .map("/id_token_claims/groups", "8f1c3b7e-admins", "admin")
+///     .map("/member/roles", "1100223344", "moderator")
+///     .copy("/realm_access/roles");
+///
+/// let profile = json!({
+///     "id_token_claims": { "groups": ["8f1c3b7e-admins", "8f1c3b7e-staff"] },
+///     "realm_access": { "roles": ["editor"] },
+/// });
+/// assert_eq!(mapping.roles(&profile), vec!["admin", "editor"]);
+/// ```
+#[derive(Clone, Default)]
+pub struct RoleMapping {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+enum Rule {
+    /// Grants the role when the claim equals the value, or contains it.
+    Map {
+        path: String,
+        value: Value,
+        role: String,
+    },
+    /// Grants the claim values as roles.
+    Copy { path: String },
+}
+
+impl RoleMapping {
+    /// Grants `role` when the claim at `path` equals `value` or, for a list, contains it.
+    pub fn map<V>(mut self, path: &str, value: V, role: &str) -> Self
+    where
+        V: Into<Value>,
+    {
+        self.rules.push(Rule::Map {
+            path: path.to_string(),
+            value: value.into(),
+            role: role.to_string(),
+        });
+        self
+    }
+
+    /// Grants the values of the claim at `path` as roles, e.g. the Keycloak `realm_access.roles`.
+    pub fn copy(mut self, path: &str) -> Self {
+        self.rules.push(Rule::Copy {
+            path: path.to_string(),
+        });
+        self
+    }
+
+    /// Returns the roles granted to the `profile`, without duplicates, in the order of the rules.
+    pub fn roles(&self, profile: &Value) -> Vec<String> {
+        let mut roles = Vec::new();
+        let mut grant = |role: &str| {
+            if !roles.iter().any(|granted| granted == role) {
+                roles.push(role.to_string());
+            }
+        };
+
+        for rule in &self.rules {
+            match rule {
+                Rule::Map { path, value, role } => {
+                    let granted = match lookup(profile, path) {
+                        Some(Value::Array(claims)) => claims.contains(value),
+                        Some(claim) => claim == value,
+                        None => false,
+                    };
+                    if granted {
+                        grant(role);
+                    }
+                }
+                Rule::Copy { path } => match lookup(profile, path) {
+                    Some(Value::Array(claims)) => {
+                        claims.iter().filter_map(Value::as_str).for_each(&mut grant)
+                    }
+                    Some(Value::String(claim)) => grant(claim),
+                    _ => {}
+                },
+            }
+        }
+
+        roles
+    }
+}
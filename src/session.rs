@@ -0,0 +1,551 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::passport::now;
+
+/// The length of the AES-GCM nonce prepended to the encrypted cookies.
+const NONCE_LENGTH: usize = 12;
+
+/// The largest `Set-Cookie` header value browsers are required to keep, larger cookies are silently dropped.
+const MAX_COOKIE_LENGTH: usize = 4096;
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    /// Only honored by the browsers along with `Secure`.
+    None,
+}
+
+/// The name and attributes of a session cookie. Session cookies are always `HttpOnly`, and `Secure` unless
+/// turned off for local development.
+#[derive(Debug, Clone)]
+pub struct CookieOptions {
+    name: String,
+    path: String,
+    domain: Option<String>,
+    same_site: SameSite,
+    secure: bool,
+}
+
+impl CookieOptions {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: String::from("/"),
+            domain: None,
+            same_site: SameSite::Lax,
+            secure: true,
+        }
+    }
+
+    pub fn set_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn set_domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn set_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Sets whether the cookie is only sent over https, `true` by default.
+    pub fn set_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// The `Set-Cookie` header value of the cookie.
+    fn header(&self, value: &str, max_age: u64) -> String {
+        let mut header = format!(
+            "{}={value}; Path={}; Max-Age={max_age}; HttpOnly",
+            self.name, self.path
+        );
+        if let Some(domain) = &self.domain {
+            header.push_str(&format!("; Domain={domain}"));
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        header.push_str(match self.same_site {
+            SameSite::Strict => "; SameSite=Strict",
+            SameSite::Lax => "; SameSite=Lax",
+            SameSite::None => "; SameSite=None",
+        });
+        header
+    }
+
+    /// Finds the cookie in a `Cookie` header.
+    fn find<'a>(&self, header: &'a str) -> Result<&'a str, Error> {
+        header
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == self.name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| Error::InvalidSession(String::from("cookie is missing")))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    exp: u64,
+    value: T,
+}
+
+/// Keeps the session in a cookie encrypted and authenticated with AES-256-GCM, so it can be neither read nor
+/// forged by the browser. The expiry is rolling: [`CookieSession::verify`] returns the cookie renewed for another
+/// max age, to be sent back along with the response.
+///
+/// The cookie can't exceed 4096 bytes, its attributes included. The encryption and encoding take about a third more
+/// than the JSON of the value, so keep it small, e.g. the user id and roles rather than the whole profile.
+///
+/// ```rust
+/// use passport_strategies::session::{CookieOptions, CookieSession};
+/// use serde_json::{json, Value};
+///
+/// let session = CookieSession::new(CookieOptions::new("session"), &[7; 32])
+///     .unwrap()
+///     .set_max_age(3600);
+///
+/// let set_cookie = session.issue(&json!({ "id": "42", "roles": ["admin"] })).unwrap();
+/// let cookie = set_cookie.split(';').next().unwrap();
+///
+/// let (user, _renewed) = session.verify::<Value>(cookie).unwrap();
+/// assert_eq!(user["id"], "42");
+/// assert!(session.logout().contains("Max-Age=0"));
+/// ```
+#[derive(Clone)]
+pub struct CookieSession {
+    options: CookieOptions,
+    cipher: Aes256Gcm,
+    max_age: u64,
+}
+
+impl CookieSession {
+    /// Creates the session with a 32 bytes `key`, e.g. generated once with `openssl rand 32` and kept secret.
+    pub fn new(options: CookieOptions, key: &[u8]) -> Result<Self, Error> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|_| Error::InvalidSession(String::from("key must be 32 bytes")))?;
+
+        Ok(Self {
+            options,
+            cipher,
+            max_age: 24 * 60 * 60,
+        })
+    }
+
+    /// Sets how long, in seconds, the session lasts without being verified, a day by default.
+    pub fn set_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Returns the `Set-Cookie` header value starting a session holding `value`, e.g. the
+    /// [`Oauth2ServerResponse`](crate::passport::Oauth2ServerResponse) profile or the application user. Fails with
+    /// [`Error::InvalidSession`] when the header value exceeds 4096 bytes, as the browsers would drop the cookie.
+    pub fn issue<T>(&self, value: &T) -> Result<String, Error>
+    where
+        T: Serialize,
+    {
+        let envelope = Envelope {
+            exp: now() + self.max_age,
+            value,
+        };
+        let plaintext = serde_json::to_vec(&envelope)
+            .map_err(|error| Error::InvalidSession(error.to_string()))?;
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: self.options.name.as_bytes(),
+                },
+            )
+            .map_err(|error| Error::InvalidSession(error.to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        let header = self
+            .options
+            .header(&URL_SAFE_NO_PAD.encode(sealed), self.max_age);
+        if header.len() > MAX_COOKIE_LENGTH {
+            return Err(Error::InvalidSession(format!(
+                "cookie exceeds {MAX_COOKIE_LENGTH} bytes"
+            )));
+        }
+        Ok(header)
+    }
+
+    /// Verifies the session cookie found in the `Cookie` header, returning its value along with the `Set-Cookie`
+    /// header value of the renewed cookie.
+    pub fn verify<T>(&self, cookie_header: &str) -> Result<(T, String), Error>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let sealed = URL_SAFE_NO_PAD
+            .decode(self.options.find(cookie_header)?)
+            .map_err(|error| Error::InvalidSession(error.to_string()))?;
+        if sealed.len() < NONCE_LENGTH {
+            return Err(Error::InvalidSession(String::from("cookie is truncated")));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: self.options.name.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::InvalidSession(String::from("cookie is forged")))?;
+
+        let envelope = serde_json::from_slice::<Envelope<T>>(&plaintext)
+            .map_err(|error| Error::InvalidSession(error.to_string()))?;
+        if envelope.exp < now() {
+            return Err(Error::SessionExpired);
+        }
+
+        let renewed = self.issue(&envelope.value)?;
+        Ok((envelope.value, renewed))
+    }
+
+    /// Returns the `Set-Cookie` header value clearing the session cookie.
+    pub fn logout(&self) -> String {
+        self.options.header("", 0)
+    }
+}
+
+/// Issues the session as a signed JWT, either sent as a bearer token or kept in a cookie. The claims are the
+/// registered `sub`, `iat`, `exp` and, when set, `iss` and `aud`, the fixed claims set with
+/// [`JwtSession::set_claim`], and the fields of the value passed to [`JwtSession::issue`].
+///
+/// ```rust,ignore
+/// let session = JwtSession::hs256(secret.as_bytes())
+///     .set_issuer("https://app.ourco.com")
+///     .set_ttl(15 * 60)
+///     .set_claim("tenant", "ourco");
+///
+/// let token = session.issue(&user.id, &json!({ "roles": response.roles }))?;
+/// let claims = session.verify::<Claims>(&token)?;
+/// ```
+#[derive(Clone)]
+pub struct JwtSession {
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    issuer: Option<String>,
+    audience: Option<String>,
+    ttl: u64,
+    claims: Map<String, Value>,
+    cookie: CookieOptions,
+}
+
+impl JwtSession {
+    /// Signs the tokens with the keys of the `algorithm`, e.g. `RS256` or `ES256`.
+    pub fn new(algorithm: Algorithm, encoding: EncodingKey, decoding: DecodingKey) -> Self {
+        Self {
+            algorithm,
+            encoding,
+            decoding,
+            issuer: None,
+            audience: None,
+            ttl: 60 * 60,
+            claims: Map::new(),
+            cookie: CookieOptions::new("passport_token"),
+        }
+    }
+
+    /// Signs the tokens with HMAC-SHA256 and the shared `secret`.
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self::new(
+            Algorithm::HS256,
+            EncodingKey::from_secret(secret),
+            DecodingKey::from_secret(secret),
+        )
+    }
+
+    /// Sets the `iss` claim, which is then required.
+    pub fn set_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Sets the `aud` claim, which is then required.
+    pub fn set_audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Sets how long, in seconds, the tokens are valid, an hour by default.
+    pub fn set_ttl(mut self, ttl: u64) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Adds a claim to every token.
+    pub fn set_claim<V>(mut self, name: &str, value: V) -> Self
+    where
+        V: Into<Value>,
+    {
+        self.claims.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Sets the cookie the tokens are kept in, `passport_token` by default.
+    pub fn set_cookie(mut self, cookie: CookieOptions) -> Self {
+        self.cookie = cookie;
+        self
+    }
+
+    /// Returns a token for the `subject`, with the fields of `value` as claims.
+    pub fn issue<T>(&self, subject: &str, value: &T) -> Result<String, Error>
+    where
+        T: Serialize,
+    {
+        let mut claims = self.claims.clone();
+        match serde_json::to_value(value)
+            .map_err(|error| Error::InvalidSession(error.to_string()))?
+        {
+            Value::Object(fields) => claims.extend(fields),
+            Value::Null => {}
+            _ => {
+                return Err(Error::InvalidSession(String::from(
+                    "claims must be an object",
+                )))
+            }
+        }
+
+        let iat = now();
+        claims.insert(String::from("sub"), Value::from(subject));
+        claims.insert(String::from("iat"), Value::from(iat));
+        claims.insert(String::from("exp"), Value::from(iat + self.ttl));
+        if let Some(issuer) = &self.issuer {
+            claims.insert(String::from("iss"), Value::from(issuer.as_str()));
+        }
+        if let Some(audience) = &self.audience {
+            claims.insert(String::from("aud"), Value::from(audience.as_str()));
+        }
+
+        Ok(jsonwebtoken::encode(
+            &Header::new(self.algorithm),
+            &claims,
+            &self.encoding,
+        )?)
+    }
+
+    /// Verifies the signature, the expiry, and the issuer and audience when set, and returns the claims.
+    pub fn verify<T>(&self, token: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut validation = Validation::new(self.algorithm);
+        match &self.issuer {
+            Some(issuer) => validation.set_issuer(&[issuer]),
+            None => validation.iss = None,
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        match jsonwebtoken::decode::<T>(token, &self.decoding, &validation) {
+            Ok(data) => Ok(data.claims),
+            Err(error) if *error.kind() == ErrorKind::ExpiredSignature => {
+                Err(Error::SessionExpired)
+            }
+            Err(error) => Err(Error::Jwt(error)),
+        }
+    }
+
+    /// Returns the `Set-Cookie` header value keeping the `token` in the cookie.
+    pub fn cookie(&self, token: &str) -> String {
+        self.cookie.header(token, self.ttl)
+    }
+
+    /// Like [`JwtSession::verify`], for the token kept in the cookie found in the `Cookie` header.
+    pub fn verify_cookie<T>(&self, cookie_header: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.verify(self.cookie.find(cookie_header)?)
+    }
+
+    /// Returns the `Set-Cookie` header value clearing the cookie. The tokens sent as bearer tokens can't be
+    /// revoked and stay valid until they expire, keep their ttl short.
+    pub fn logout(&self) -> String {
+        self.cookie.header("", 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn session(name: &str, key: &[u8]) -> CookieSession {
+        CookieSession::new(CookieOptions::new(name), key).unwrap()
+    }
+
+    /// The `name=value` pair of a `Set-Cookie` header value, as sent back in the `Cookie` header.
+    fn pair(set_cookie: &str) -> String {
+        set_cookie.split(';').next().unwrap().to_string()
+    }
+
+    /// Seals the `envelope` the way [`CookieSession::issue`] does.
+    fn seal(session: &CookieSession, envelope: &Envelope<Value>) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = session
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &serde_json::to_vec(envelope).unwrap(),
+                    aad: session.options.name.as_bytes(),
+                },
+            )
+            .unwrap();
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        format!(
+            "{}={}",
+            session.options.name,
+            URL_SAFE_NO_PAD.encode(sealed)
+        )
+    }
+
+    #[test]
+    fn rejects_a_cookie_too_large() {
+        let session = session("session", &KEY);
+
+        let set_cookie = session.issue(&json!({ "id": "x".repeat(2900) })).unwrap();
+        assert!(set_cookie.len() <= MAX_COOKIE_LENGTH);
+
+        assert!(matches!(
+            session.issue(&json!({ "id": "x".repeat(3100) })),
+            Err(Error::InvalidSession(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_tampered_cookie() {
+        let session = session("session", &KEY);
+        let cookie = pair(&session.issue(&json!({ "id": "42" })).unwrap());
+
+        let (name, value) = cookie.split_once('=').unwrap();
+        let mut sealed = URL_SAFE_NO_PAD.decode(value).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        let tampered = format!("{name}={}", URL_SAFE_NO_PAD.encode(sealed));
+
+        assert!(matches!(
+            session.verify::<Value>(&tampered),
+            Err(Error::InvalidSession(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_cookie_of_another_name_or_key() {
+        let issued = pair(
+            &session("session", &KEY)
+                .issue(&json!({ "id": "42" }))
+                .unwrap(),
+        );
+        let value = issued.split_once('=').unwrap().1;
+
+        // The name is the associated data, the cookie can't be replayed as another cookie.
+        let renamed = format!("admin={value}");
+        assert!(matches!(
+            session("admin", &KEY).verify::<Value>(&renamed),
+            Err(Error::InvalidSession(_))
+        ));
+        assert!(matches!(
+            session("session", &[8; 32]).verify::<Value>(&issued),
+            Err(Error::InvalidSession(_))
+        ));
+    }
+
+    #[test]
+    fn expires_the_cookie() {
+        let session = session("session", &KEY);
+        let cookie = seal(
+            &session,
+            &Envelope {
+                exp: now() - 1,
+                value: json!({ "id": "42" }),
+            },
+        );
+
+        assert!(matches!(
+            session.verify::<Value>(&cookie),
+            Err(Error::SessionExpired)
+        ));
+    }
+
+    #[test]
+    fn checks_the_jwt_issuer_and_audience() {
+        let session = JwtSession::hs256(b"secret")
+            .set_issuer("https://app.ourco.com")
+            .set_audience("app");
+        let token = session.issue("42", &json!({ "roles": ["admin"] })).unwrap();
+
+        let claims = session.verify::<Value>(&token).unwrap();
+        assert_eq!(claims["sub"], "42");
+        assert_eq!(claims["roles"][0], "admin");
+
+        let issuer = JwtSession::hs256(b"secret")
+            .set_issuer("https://evil.com")
+            .set_audience("app");
+        assert!(matches!(issuer.verify::<Value>(&token), Err(Error::Jwt(_))));
+
+        let audience = JwtSession::hs256(b"secret")
+            .set_issuer("https://app.ourco.com")
+            .set_audience("admin");
+        assert!(matches!(
+            audience.verify::<Value>(&token),
+            Err(Error::Jwt(_))
+        ));
+    }
+
+    #[test]
+    fn expires_the_jwt() {
+        let session = JwtSession::hs256(b"secret");
+        // Past the leeway of the validation.
+        let claims = json!({ "sub": "42", "iat": now() - 600, "exp": now() - 300 });
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            session.verify::<Value>(&token),
+            Err(Error::SessionExpired)
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use oauth2::{AuthType, Scope};
+
+use super::{
+    AtlassianStrategy, EndpointEnricher, TokenExchange, ATLASSIAN_AUDIENCE, ATLASSIAN_AUTH_URL,
+    ATLASSIAN_REQUEST_URL, ATLASSIAN_SCOPES, ATLASSIAN_TOKEN_URL,
+};
+use crate::passport::ResponseMode;
+
+impl Default for AtlassianStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::from(ATLASSIAN_AUTH_URL),
+            scopes: ATLASSIAN_SCOPES
+                .iter()
+                .map(ToString::to_string)
+                .map(Scope::new)
+                .collect(),
+            token_uri: String::from(ATLASSIAN_TOKEN_URL),
+            request_uri: String::from(ATLASSIAN_REQUEST_URL),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            // The token endpoint takes the client credentials in the request body. The rotated refresh token is
+            // taken from the raw response.
+            token_exchange: TokenExchange::RequestBody,
+            auth_type: AuthType::BasicAuth,
+            // The cloudids are needed to call the Jira and Confluence APIs.
+            enrichers: vec![Arc::new(EndpointEnricher::atlassian_resources())],
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
+            userinfo_uri: None,
+            client_id_header: None,
+            auth_params: vec![
+                (String::from("audience"), String::from(ATLASSIAN_AUDIENCE)),
+                (String::from("prompt"), String::from("consent")),
+            ],
+        }
+    }
+}
@@ -1,39 +1,111 @@
 // Google
-pub(crate) const GOOGLE_AUTH_URL: &'static str = "https://accounts.google.com/o/oauth2/auth";
-pub(crate) const GOOGLE_TOKEN_URL: &'static str = "https://oauth2.googleapis.com/token";
-pub(crate) const GOOGLE_REQUEST_URL: &'static str = "https://people.googleapis.com/v1/people/me?personFields=names,emailAddresses,phoneNumbers,metadata,nicknames,photos,userDefined,skills,clientData,addresses,birthdays,calendarUrls,events,ageRanges,interests,coverPhotos,biographies,genders,imClients,memberships,locations,miscKeywords,relations,organizations,urls,userDefined,sipAddresses,occupations,locales";
+pub(crate) const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/auth";
+pub(crate) const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+pub(crate) const GOOGLE_REQUEST_URL: &str = "https://people.googleapis.com/v1/people/me?personFields=names,emailAddresses,phoneNumbers,metadata,nicknames,photos,userDefined,skills,clientData,addresses,birthdays,calendarUrls,events,ageRanges,interests,coverPhotos,biographies,genders,imClients,memberships,locations,miscKeywords,relations,organizations,urls,userDefined,sipAddresses,occupations,locales";
 
 // Microsoft
-pub(crate) const MICROSOFT_AUTH_URL: &'static str =
+pub(crate) const MICROSOFT_AUTH_URL: &str =
     "https://login.microsoftonline.com/common/oauth2/v2.0/authorize?prompt=select_account";
 
-pub(crate) const MICROSOFT_TOKEN_URL: &'static str =
+pub(crate) const MICROSOFT_TOKEN_URL: &str =
     "https://login.microsoftonline.com/common/oauth2/v2.0/token";
-pub(crate) const MICROSOFT_REQUEST_URL: &'static str = "https://graph.microsoft.com/v1.0/me";
+pub(crate) const MICROSOFT_REQUEST_URL: &str = "https://graph.microsoft.com/v1.0/me";
 
 // Github
-pub(crate) const GITHUB_AUTH_URL: &'static str = "https://github.com/login/oauth/authorize";
-pub(crate) const GITHUB_TOKEN_URL: &'static str = "https://github.com/login/oauth/access_token";
-pub(crate) const GITHUB_REQUEST_URL: &'static str = "https://api.github.com/user";
+pub(crate) const GITHUB_AUTH_URL: &str = "https://github.com/login/oauth/authorize";
+pub(crate) const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+pub(crate) const GITHUB_REQUEST_URL: &str = "https://api.github.com/user";
+pub(crate) const GITHUB_EMAILS_URL: &str = "https://api.github.com/user/emails";
+pub(crate) const GITHUB_ORGS_URL: &str = "https://api.github.com/user/orgs";
+pub(crate) const GITHUB_API_URL: &str = "https://api.github.com";
 
 // Discord
-pub(crate) const DISCORD_AUTH_URL: &'static str = "https://discord.com/oauth2/authorize";
-pub(crate) const DISCORD_TOKEN_URL: &'static str = "https://discord.com/api/oauth2/token";
-pub(crate) const DISCORD_REQUEST_URL: &'static str = "https://discord.com/api/users/@me";
+pub(crate) const DISCORD_AUTH_URL: &str = "https://discord.com/oauth2/authorize";
+pub(crate) const DISCORD_TOKEN_URL: &str = "https://discord.com/api/oauth2/token";
+pub(crate) const DISCORD_REQUEST_URL: &str = "https://discord.com/api/users/@me";
+pub(crate) const DISCORD_GUILDS_URL: &str = "https://discord.com/api/users/@me/guilds";
 
 // 42
-pub(crate) const FORTYTWO_AUTH_URL: &'static str = "https://api.intra.42.fr/oauth/authorize";
-pub(crate) const FORTYTWO_TOKEN_URL: &'static str = "https://api.intra.42.fr/oauth/token";
-pub(crate) const FORTYTWO_REQUEST_URL: &'static str = "https://api.intra.42.fr/v2/me";
+pub(crate) const FORTYTWO_AUTH_URL: &str = "https://api.intra.42.fr/oauth/authorize";
+pub(crate) const FORTYTWO_TOKEN_URL: &str = "https://api.intra.42.fr/oauth/token";
+pub(crate) const FORTYTWO_REQUEST_URL: &str = "https://api.intra.42.fr/v2/me";
 
 // Facebook
-pub(crate) const FACEBOOK_AUTH_URL: &'static str = "https://www.facebook.com/v18.0/dialog/oauth";
-pub(crate) const FACEBOOK_TOKEN_URL: &'static str =
-    "https://graph.facebook.com/v18.0/oauth/access_token";
-pub(crate) const FACEBOOK_REQUEST_URL: &'static str = "https://graph.facebook.com/me";
+pub(crate) const FACEBOOK_AUTH_URL: &str = "https://www.facebook.com/v18.0/dialog/oauth";
+pub(crate) const FACEBOOK_TOKEN_URL: &str = "https://graph.facebook.com/v18.0/oauth/access_token";
+pub(crate) const FACEBOOK_REQUEST_URL: &str = "https://graph.facebook.com/me";
 
 // Reddit
-pub(crate) const REDDIT_AUTH_URL: &'static str =
+pub(crate) const REDDIT_AUTH_URL: &str =
     "https://www.reddit.com/api/v1/authorize?duration=permanent";
-pub(crate) const REDDIT_TOKEN_URL: &'static str = "https://www.reddit.com/api/v1/access_token";
-pub(crate) const REDDIT_REQUEST_URL: &'static str = "https://oauth.reddit.com/api/v1/me";
+pub(crate) const REDDIT_TOKEN_URL: &str = "https://www.reddit.com/api/v1/access_token";
+pub(crate) const REDDIT_REQUEST_URL: &str = "https://oauth.reddit.com/api/v1/me";
+
+// Apple
+pub(crate) const APPLE_AUTH_URL: &str = "https://appleid.apple.com/auth/authorize";
+pub(crate) const APPLE_TOKEN_URL: &str = "https://appleid.apple.com/auth/token";
+pub(crate) const APPLE_KEYS_URL: &str = "https://appleid.apple.com/auth/keys";
+pub(crate) const APPLE_ISSUER: &str = "https://appleid.apple.com";
+
+// X (Twitter)
+pub(crate) const TWITTER_AUTH_URL: &str = "https://twitter.com/i/oauth2/authorize";
+pub(crate) const TWITTER_TOKEN_URL: &str = "https://api.twitter.com/2/oauth2/token";
+pub(crate) const TWITTER_REQUEST_URL: &str = "https://api.twitter.com/2/users/me?user.fields=id,name,username,created_at,description,location,pinned_tweet_id,profile_image_url,protected,public_metrics,url,verified";
+
+// Gitlab, the endpoints are relative to the base url of the instance.
+pub(crate) const GITLAB_BASE_URL: &str = "https://gitlab.com";
+pub(crate) const GITLAB_AUTH_PATH: &str = "/oauth/authorize";
+pub(crate) const GITLAB_TOKEN_PATH: &str = "/oauth/token";
+pub(crate) const GITLAB_REQUEST_PATH: &str = "/api/v4/user";
+pub(crate) const GITLAB_USERINFO_PATH: &str = "/oauth/userinfo";
+
+// Linkedin (Sign In with LinkedIn using OpenID Connect)
+pub(crate) const LINKEDIN_AUTH_URL: &str = "https://www.linkedin.com/oauth/v2/authorization";
+pub(crate) const LINKEDIN_TOKEN_URL: &str = "https://www.linkedin.com/oauth/v2/accessToken";
+pub(crate) const LINKEDIN_REQUEST_URL: &str = "https://api.linkedin.com/v2/userinfo";
+pub(crate) const LINKEDIN_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+// Twitch
+pub(crate) const TWITCH_AUTH_URL: &str = "https://id.twitch.tv/oauth2/authorize";
+pub(crate) const TWITCH_TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+pub(crate) const TWITCH_REQUEST_URL: &str = "https://api.twitch.tv/helix/users";
+
+// Slack (Sign in with Slack)
+pub(crate) const SLACK_AUTH_URL: &str = "https://slack.com/openid/connect/authorize";
+pub(crate) const SLACK_TOKEN_URL: &str = "https://slack.com/api/openid.connect.token";
+pub(crate) const SLACK_REQUEST_URL: &str = "https://slack.com/api/openid.connect.userInfo";
+pub(crate) const SLACK_TEAM_ID_CLAIM: &str = "https://slack.com/team_id";
+pub(crate) const SLACK_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+// Steam (OpenID 2.0)
+pub(crate) const STEAM_OPENID_URL: &str = "https://steamcommunity.com/openid/login";
+pub(crate) const STEAM_CLAIMED_ID_PREFIX: &str = "https://steamcommunity.com/openid/id/";
+pub(crate) const STEAM_REQUEST_URL: &str =
+    "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002/";
+pub(crate) const OPENID_NS: &str = "http://specs.openid.net/auth/2.0";
+pub(crate) const OPENID_IDENTIFIER_SELECT: &str =
+    "http://specs.openid.net/auth/2.0/identifier_select";
+
+// Telegram (Login Widget)
+pub(crate) const TELEGRAM_MAX_AGE: u64 = 86_400;
+pub(crate) const TELEGRAM_AUTH_URL: &str = "https://oauth.telegram.org/auth";
+
+// Mastodon, the endpoints are relative to the instance the user signs in with.
+pub(crate) const MASTODON_DEFAULT_INSTANCE: &str = "mastodon.social";
+pub(crate) const MASTODON_APPS_PATH: &str = "/api/v1/apps";
+pub(crate) const MASTODON_AUTH_PATH: &str = "/oauth/authorize";
+pub(crate) const MASTODON_TOKEN_PATH: &str = "/oauth/token";
+pub(crate) const MASTODON_REQUEST_PATH: &str = "/api/v1/accounts/verify_credentials";
+pub(crate) const MASTODON_SCOPES: [&str; 1] = ["read:accounts"];
+pub(crate) const MASTODON_MAX_CLIENTS: usize = 1000;
+pub(crate) const MASTODON_CONNECT_TIMEOUT: u64 = 5;
+pub(crate) const MASTODON_TIMEOUT: u64 = 15;
+
+// Atlassian
+pub(crate) const ATLASSIAN_AUTH_URL: &str = "https://auth.atlassian.com/authorize";
+pub(crate) const ATLASSIAN_TOKEN_URL: &str = "https://auth.atlassian.com/oauth/token";
+pub(crate) const ATLASSIAN_REQUEST_URL: &str = "https://api.atlassian.com/me";
+pub(crate) const ATLASSIAN_RESOURCES_URL: &str =
+    "https://api.atlassian.com/oauth/token/accessible-resources";
+pub(crate) const ATLASSIAN_AUDIENCE: &str = "api.atlassian.com";
+pub(crate) const ATLASSIAN_SCOPES: [&str; 2] = ["read:me", "offline_access"];
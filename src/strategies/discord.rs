@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use oauth2::Scope;
+use serde_json::Value;
+
+use super::{DiscordStrategy, Enricher, PAccessToken, DISCORD_GUILDS_URL};
+use crate::error::Error;
+use crate::passport::Passport;
+
+impl DiscordStrategy {
+    /// Only admits the members of the guild with the given id and, when `roles` are given, with at least one of
+    /// these role ids. The `guilds.members.read` scope is requested, and the member record (e.g. `nick`, `roles`
+    /// and `joined_at`) is added to the profile under `member`. Other users fail with [`Error::DiscordNotMember`].
+    /// Calling it again replaces the guild.
+    pub fn set_guild(mut self, guild_id: &str, roles: &[&str]) -> Self {
+        if !self
+            .scopes
+            .iter()
+            .any(|scope| scope.as_str() == "guilds.members.read")
+        {
+            self.scopes
+                .push(Scope::new(String::from("guilds.members.read")));
+        }
+
+        self.remove_enricher(GUILD_MEMBER_KEY)
+            .add_enricher(GuildMember {
+                guilds_url: String::from(DISCORD_GUILDS_URL),
+                guild_id: guild_id.to_string(),
+                roles: roles.iter().map(ToString::to_string).collect(),
+            })
+    }
+}
+
+/// The profile key of the member record.
+const GUILD_MEMBER_KEY: &str = "member";
+
+/// Checks the membership of the user in a guild.
+struct GuildMember {
+    guilds_url: String,
+    guild_id: String,
+    roles: Vec<String>,
+}
+
+#[async_trait]
+impl Enricher for GuildMember {
+    async fn enrich(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error> {
+        let response = http
+            .get(format!("{}/{}/member", self.guilds_url, self.guild_id))
+            .bearer_auth(&access_token.0)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        // Discord answers `404 Not Found` (Unknown Guild) when the user is not a member.
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::DiscordNotMember(self.guild_id.clone()));
+        }
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        let member = response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))?;
+
+        if !self.roles.is_empty() {
+            let has_role = member["roles"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .any(|role| self.roles.iter().any(|required| required == role));
+            if !has_role {
+                return Err(Error::DiscordNotMember(self.guild_id.clone()));
+            }
+        }
+
+        if let Some(map) = profile.as_object_mut() {
+            map.insert(String::from(GUILD_MEMBER_KEY), member);
+        }
+
+        Ok(())
+    }
+
+    fn key(&self) -> Option<&str> {
+        Some(GUILD_MEMBER_KEY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::strategies::Strategy;
+    use crate::testing::serve;
+
+    fn guild_member(url: &str, roles: &[&str]) -> GuildMember {
+        GuildMember {
+            guilds_url: format!("{url}/users/@me/guilds"),
+            guild_id: String::from("42"),
+            roles: roles.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    async fn enrich(guild_member: &GuildMember) -> Result<Value, Error> {
+        let mut profile = json!({ "id": "1" });
+        guild_member
+            .enrich(
+                &reqwest::Client::new(),
+                &PAccessToken(String::from("token")),
+                &mut profile,
+            )
+            .await
+            .map(|_| profile)
+    }
+
+    #[test]
+    fn replaces_the_guild() {
+        let strategy =
+            DiscordStrategy::new("id", "secret", &["identify"], "https://ourco.com/callback")
+                .set_guild("1", &[])
+                .set_guild("2", &[]);
+
+        assert_eq!(strategy.enrichers().len(), 1);
+        let scopes = strategy.scopes();
+        assert_eq!(
+            scopes
+                .iter()
+                .filter(|scope| scope.as_str() == "guilds.members.read")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_the_member() {
+        let member = json!({ "nick": "ada", "roles": ["admin"] });
+        let (url, requests) =
+            serve(vec![("/users/@me/guilds/42/member", 200, member.clone())]).await;
+
+        let profile = enrich(&guild_member(&url, &["admin", "mod"]))
+            .await
+            .unwrap();
+        assert_eq!(profile["member"], member);
+        assert!(requests.lock().unwrap()[0]
+            .to_ascii_lowercase()
+            .contains("authorization: bearer token"));
+    }
+
+    #[tokio::test]
+    async fn rejects_other_users() {
+        let unknown_guild = json!({ "message": "Unknown Guild", "code": 10004 });
+        let (url, _) = serve(vec![("/users/@me/guilds/42/member", 404, unknown_guild)]).await;
+
+        assert!(matches!(
+            enrich(&guild_member(&url, &[])).await,
+            Err(Error::DiscordNotMember(guild)) if guild == "42"
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_members_without_the_roles() {
+        let member = json!({ "nick": "ada", "roles": ["member"] });
+        let (url, _) = serve(vec![("/users/@me/guilds/42/member", 200, member)]).await;
+
+        assert!(matches!(
+            enrich(&guild_member(&url, &["admin"])).await,
+            Err(Error::DiscordNotMember(_))
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::{
+    PAccessToken, ATLASSIAN_RESOURCES_URL, DISCORD_GUILDS_URL, GITHUB_EMAILS_URL, GITHUB_ORGS_URL,
+};
+use crate::error::Error;
+use crate::passport::Passport;
+
+/// Makes extra calls with the access token once the profile is retrieved, and merges their results into it.
+/// Enrichers are added to a strategy, e.g. with `GithubStrategy::add_enricher`, and run in order by
+/// [`Strategy::enrich`](super::Strategy::enrich).
+#[async_trait]
+pub trait Enricher: Send + Sync {
+    async fn enrich(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error>;
+
+    /// The key the enricher is known by, usually the profile key it adds its result under, for `remove_enricher`
+    /// to find it.
+    fn key(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Requests an endpoint with the access token as a bearer token, and adds the response to the profile under `key`.
+///
+/// ```rust,ignore
+/// let strategy = GithubStrategy::new(client_id, client_secret, &["user:email", "read:org"], redirect_url)
+///     .add_enricher(EndpointEnricher::github_emails())
+///     .add_enricher(EndpointEnricher::github_orgs());
+/// ```
+#[derive(Clone)]
+pub struct EndpointEnricher {
+    pub(crate) key: String,
+    pub(crate) url: String,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl EndpointEnricher {
+    pub fn new(key: &str, url: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            url: url.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header sent along with the request.
+    pub fn set_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// The emails of a Github user under `emails`, including the private ones. Needs the `user:email` scope.
+    pub fn github_emails() -> Self {
+        Self::new("emails", GITHUB_EMAILS_URL)
+    }
+
+    /// The organizations of a Github user under `orgs`. Needs the `read:org` scope for the private memberships.
+    pub fn github_orgs() -> Self {
+        Self::new("orgs", GITHUB_ORGS_URL)
+    }
+
+    /// The guilds of a Discord user under `guilds`. Needs the `guilds` scope.
+    pub fn discord_guilds() -> Self {
+        Self::new("guilds", DISCORD_GUILDS_URL)
+    }
+
+    /// The cloud sites an Atlassian user granted access to under `accessible_resources`, each with its `id`
+    /// (the cloudid).
+    pub fn atlassian_resources() -> Self {
+        Self::new("accessible_resources", ATLASSIAN_RESOURCES_URL)
+    }
+}
+
+#[async_trait]
+impl Enricher for EndpointEnricher {
+    async fn enrich(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error> {
+        let mut request = http
+            .get(&self.url)
+            .bearer_auth(&access_token.0)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT);
+
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        let value = response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))?;
+
+        if let Some(map) = profile.as_object_mut() {
+            map.insert(self.key.clone(), value);
+        }
+
+        Ok(())
+    }
+
+    fn key(&self) -> Option<&str> {
+        Some(&self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::strategies::{AtlassianStrategy, GithubStrategy, Strategy};
+    use crate::testing::serve;
+
+    /// Appends its name to the `calls` of the profile, or fails.
+    struct Step(&'static str, bool);
+
+    #[async_trait]
+    impl Enricher for Step {
+        async fn enrich(
+            &self,
+            _http: &reqwest::Client,
+            _access_token: &PAccessToken,
+            profile: &mut Value,
+        ) -> Result<(), Error> {
+            if !self.1 {
+                return Err(Error::MissingProfile);
+            }
+            if let Some(Value::Array(calls)) = profile.get_mut("calls") {
+                calls.push(Value::from(self.0));
+            }
+            Ok(())
+        }
+
+        fn key(&self) -> Option<&str> {
+            Some(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_the_enrichers_in_order() {
+        let (url, _) = serve(vec![(
+            "/user/emails",
+            200,
+            json!([{ "email": "ada@ourco.com" }]),
+        )])
+        .await;
+        let strategy = GithubStrategy::new("id", "secret", &[], "https://ourco.com/callback")
+            .add_enricher(Step("first", true))
+            .add_enricher(EndpointEnricher::new(
+                "emails",
+                &format!("{url}/user/emails"),
+            ))
+            .add_enricher(Step("second", true));
+
+        let mut profile = json!({ "login": "ada", "calls": [] });
+        strategy
+            .enrich(
+                &reqwest::Client::new(),
+                &PAccessToken(String::from("token")),
+                &mut profile,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            profile,
+            json!({
+                "login": "ada",
+                "calls": ["first", "second"],
+                "emails": [{ "email": "ada@ourco.com" }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_first_failure() {
+        let strategy = GithubStrategy::new("id", "secret", &[], "https://ourco.com/callback")
+            .add_enricher(Step("first", true))
+            .add_enricher(Step("failing", false))
+            .add_enricher(Step("second", true));
+
+        let mut profile = json!({ "calls": [] });
+        let result = strategy
+            .enrich(
+                &reqwest::Client::new(),
+                &PAccessToken(String::from("token")),
+                &mut profile,
+            )
+            .await;
+
+        assert!(matches!(result, Err(Error::MissingProfile)));
+        assert_eq!(profile["calls"], json!(["first"]));
+    }
+
+    #[test]
+    fn removes_the_enrichers() {
+        let atlassian = AtlassianStrategy::new("id", "secret", &[], "https://ourco.com/callback");
+        assert_eq!(atlassian.enrichers().len(), 1);
+        assert!(atlassian
+            .remove_enricher("accessible_resources")
+            .enrichers()
+            .is_empty());
+
+        let github = GithubStrategy::new("id", "secret", &[], "https://ourco.com/callback")
+            .add_enricher(EndpointEnricher::github_emails())
+            .add_enricher(EndpointEnricher::github_orgs())
+            .remove_enricher("emails");
+        let keys = github
+            .enrichers()
+            .iter()
+            .map(|enricher| enricher.key().map(String::from))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![Some(String::from("orgs"))]);
+        assert!(github.clear_enrichers().enrichers().is_empty());
+    }
+}
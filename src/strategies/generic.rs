@@ -0,0 +1,489 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use oauth2::{AuthType, Scope, TokenUrl};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{Enricher, Strategy};
+use crate::error::Error;
+use crate::passport::Passport;
+
+/// The metadata of a client, sent to the registration endpoint of the provider
+/// ([RFC 7591](https://www.rfc-editor.org/rfc/rfc7591#section-2)).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirect_uris: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    /// Space separated scopes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grant_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub response_types: Vec<String>,
+    /// `client_secret_basic`, `client_secret_post` or `none`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_endpoint_auth_method: Option<String>,
+    /// Any other metadata, e.g. `client_uri` or `contacts`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The client registered by the provider, along with what is needed to manage it
+/// ([RFC 7592](https://www.rfc-editor.org/rfc/rfc7592)). Keep it to delete the client later on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ClientRegistration {
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id_issued_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret_expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_client_uri: Option<String>,
+    #[serde(flatten)]
+    pub metadata: ClientMetadata,
+}
+
+/// Any OAuth 2.0 provider, e.g. Keycloak or Okta, given its endpoints with [`GenericStrategy::set_endpoints`].
+///
+/// The client is either created beforehand and given to [`GenericStrategy::new`], or registered with the
+/// provider by [`GenericStrategy::register_client`].
+///
+/// ```rust,ignore
+/// let strategy = GenericStrategy::default()
+///     .set_endpoints(auth_url, token_url, userinfo_url)
+///     .register_client(registration_endpoint, &metadata, Some(initial_access_token))
+///     .await?;
+/// let passport = passport.strategize(Choice::Custom(String::from("preview")), strategy.clone())?;
+///
+/// // On teardown
+/// strategy.delete_registration().await?;
+/// ```
+#[derive(Clone)]
+pub struct GenericStrategy {
+    pub(crate) client_id: String,
+    pub(crate) client_secret: String,
+    pub(crate) auth_uri: String,
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) request_uri: String,
+    pub(crate) token_uri: String,
+    pub(crate) redirect_uri: String,
+    pub(crate) auth_type: AuthType,
+    pub(crate) registration: Option<ClientRegistration>,
+    pub(crate) enrichers: Vec<Arc<dyn Enricher>>,
+}
+
+impl Default for GenericStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::new(),
+            scopes: Vec::new(),
+            request_uri: String::new(),
+            token_uri: String::new(),
+            redirect_uri: String::new(),
+            auth_type: AuthType::BasicAuth,
+            registration: None,
+            enrichers: Vec::new(),
+        }
+    }
+}
+
+impl GenericStrategy {
+    pub fn new(client_id: &str, client_secret: &str, scopes: &[&str], redirect_uri: &str) -> Self {
+        let mut strategy = Self::default();
+        strategy.client_id.push_str(client_id);
+        strategy.client_secret.push_str(client_secret);
+        strategy.redirect_uri.push_str(redirect_uri);
+
+        strategy
+            .scopes
+            .extend(scopes.iter().map(ToString::to_string).map(Scope::new));
+        strategy
+    }
+
+    /// Sets the authorization, token and profile endpoints of the provider.
+    pub fn set_endpoints(mut self, auth_url: &str, token_url: &str, request_uri: &str) -> Self {
+        self.auth_uri = auth_url.to_string();
+        self.token_uri = token_url.to_string();
+        self.request_uri = request_uri.to_string();
+        self
+    }
+
+    /// Sets how the client credentials are sent to the token endpoint. Defaults to [`AuthType::BasicAuth`].
+    pub fn set_auth_type(mut self, auth_type: AuthType) -> Self {
+        self.auth_type = auth_type;
+        self
+    }
+
+    /// Adds an enricher run once the profile is retrieved.
+    pub fn add_enricher<E>(mut self, enricher: E) -> Self
+    where
+        E: Enricher + 'static,
+    {
+        self.enrichers.push(Arc::new(enricher));
+        self
+    }
+
+    /// Removes the enrichers known by `key`.
+    pub fn remove_enricher(mut self, key: &str) -> Self {
+        self.enrichers
+            .retain(|enricher| enricher.key() != Some(key));
+        self
+    }
+
+    /// Removes all the enrichers.
+    pub fn clear_enrichers(mut self) -> Self {
+        self.enrichers.clear();
+        self
+    }
+
+    /// Sets the client from a `registration`, e.g. one kept from an earlier [`GenericStrategy::register_client`].
+    /// The redirect url is the first of its `redirect_uris`.
+    pub fn set_registration(mut self, registration: ClientRegistration) -> Self {
+        let metadata = &registration.metadata;
+        self.client_id = registration.client_id.clone();
+        self.client_secret = registration.client_secret.clone().unwrap_or_default();
+        if let Some(redirect_uri) = metadata.redirect_uris.first() {
+            self.redirect_uri = redirect_uri.clone();
+        }
+        if let Some(scope) = metadata.scope.as_deref() {
+            self.scopes = scope
+                .split_whitespace()
+                .map(ToString::to_string)
+                .map(Scope::new)
+                .collect();
+        }
+        self.auth_type = match metadata.token_endpoint_auth_method.as_deref() {
+            Some("client_secret_post") => AuthType::RequestBody,
+            // A public client, the client_id alone is sent in the request body.
+            Some("none") => {
+                self.client_secret.clear();
+                AuthType::RequestBody
+            }
+            _ => AuthType::BasicAuth,
+        };
+
+        self.registration = Some(registration);
+        self
+    }
+
+    /// The registration of the client, if it was registered by [`GenericStrategy::register_client`] or set
+    /// by [`GenericStrategy::set_registration`].
+    pub fn registration(&self) -> Option<&ClientRegistration> {
+        self.registration.as_ref()
+    }
+
+    /// Registers a client with `metadata` at the `registration_endpoint` of the provider
+    /// ([RFC 7591](https://www.rfc-editor.org/rfc/rfc7591#section-3)), and returns the strategy using it.
+    /// `initial_access_token` is required by providers that do not allow open registration.
+    pub async fn register_client(
+        self,
+        registration_endpoint: &str,
+        metadata: &ClientMetadata,
+        initial_access_token: Option<&str>,
+    ) -> Result<Self, Error> {
+        let request = reqwest::Client::new()
+            .post(registration_endpoint)
+            .json(metadata);
+
+        let registration = registration_request(request, initial_access_token)
+            .await?
+            .ok_or(Error::MissingRegistration)?;
+        Ok(self.set_registration(registration))
+    }
+
+    /// Reads the current registration of the client from the provider.
+    pub async fn read_registration(self) -> Result<Self, Error> {
+        let (uri, token) = self.registration_management()?;
+        let request = reqwest::Client::new().get(uri);
+
+        let registration = registration_request(request, Some(&token))
+            .await?
+            .ok_or(Error::MissingRegistration)?;
+        Ok(self.with_registration(registration))
+    }
+
+    /// Replaces the metadata of the client with `metadata`.
+    pub async fn update_registration(self, metadata: &ClientMetadata) -> Result<Self, Error> {
+        let (uri, token) = self.registration_management()?;
+
+        // The client_id, and client_secret if any, are sent along with the metadata.
+        let mut body = serde_json::to_value(metadata)?;
+        if let Some(map) = body.as_object_mut() {
+            map.insert(
+                String::from("client_id"),
+                Value::from(self.client_id.clone()),
+            );
+            if !self.client_secret.is_empty() {
+                map.insert(
+                    String::from("client_secret"),
+                    Value::from(self.client_secret.clone()),
+                );
+            }
+        }
+        let request = reqwest::Client::new().put(uri).json(&body);
+
+        let registration = registration_request(request, Some(&token))
+            .await?
+            .ok_or(Error::MissingRegistration)?;
+        Ok(self.with_registration(registration))
+    }
+
+    /// Deletes the client from the provider.
+    pub async fn delete_registration(&self) -> Result<(), Error> {
+        let (uri, token) = self.registration_management()?;
+        let request = reqwest::Client::new().delete(uri);
+
+        registration_request(request, Some(&token)).await?;
+        Ok(())
+    }
+
+    /// The `registration_client_uri` and `registration_access_token` used to manage the client.
+    fn registration_management(&self) -> Result<(String, String), Error> {
+        let registration = self
+            .registration
+            .as_ref()
+            .ok_or(Error::MissingRegistration)?;
+        match (
+            registration.registration_client_uri.clone(),
+            registration.registration_access_token.clone(),
+        ) {
+            (Some(uri), Some(token)) => Ok((uri, token)),
+            _ => Err(Error::MissingRegistration),
+        }
+    }
+
+    /// Keeps the management credentials of the current registration when the provider leaves them out of the
+    /// response, which RFC 7592 allows.
+    fn with_registration(self, mut registration: ClientRegistration) -> Self {
+        if let Some(current) = self.registration.as_ref() {
+            registration.registration_access_token = registration
+                .registration_access_token
+                .or_else(|| current.registration_access_token.clone());
+            registration.registration_client_uri = registration
+                .registration_client_uri
+                .or_else(|| current.registration_client_uri.clone());
+            registration.client_secret = registration
+                .client_secret
+                .or_else(|| current.client_secret.clone());
+        }
+        self.set_registration(registration)
+    }
+}
+
+/// Sends a registration `request` with the bearer `token`, and returns the registration in the response, if any.
+async fn registration_request(
+    request: reqwest::RequestBuilder,
+    token: Option<&str>,
+) -> Result<Option<ClientRegistration>, Error> {
+    let mut request = request.header(reqwest::header::USER_AGENT, Passport::USER_AGENT);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+    }
+
+    if response.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    response
+        .json::<ClientRegistration>()
+        .await
+        .map(Some)
+        .map_err(|error| Error::InvalidResponse(error.to_string()))
+}
+
+#[async_trait]
+impl Strategy for GenericStrategy {
+    fn request_uri(&self) -> String {
+        self.request_uri.clone()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        self.scopes.clone()
+    }
+
+    fn client_id(&self) -> String {
+        self.client_id.clone()
+    }
+
+    fn client_secret(&self) -> String {
+        self.client_secret.clone()
+    }
+
+    fn auth_url(&self) -> String {
+        self.auth_uri.clone()
+    }
+
+    fn redirect_url(&self) -> String {
+        self.redirect_uri.clone()
+    }
+
+    fn auth_type(&self) -> AuthType {
+        self.auth_type.clone()
+    }
+
+    fn enrichers(&self) -> Vec<Arc<dyn Enricher>> {
+        self.enrichers.clone()
+    }
+
+    fn token_url(&self) -> Result<TokenUrl, Error> {
+        match TokenUrl::new(self.token_uri.clone()) {
+            Ok(token) => Ok(token),
+            Err(err) => Err(Error::ParseError(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn registration(value: Value) -> ClientRegistration {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn serializes_the_metadata() {
+        let mut metadata = ClientMetadata {
+            redirect_uris: vec![String::from("https://example.com/callback")],
+            client_name: Some(String::from("preview")),
+            ..Default::default()
+        };
+        metadata
+            .extra
+            .insert(String::from("client_uri"), json!("https://example.com"));
+
+        assert_eq!(
+            serde_json::to_value(&metadata).unwrap(),
+            json!({
+                "redirect_uris": ["https://example.com/callback"],
+                "client_name": "preview",
+                "client_uri": "https://example.com",
+            })
+        );
+    }
+
+    #[test]
+    fn deserializes_the_registration() {
+        let registration = registration(json!({
+            "client_id": "id",
+            "client_secret": "secret",
+            "client_secret_expires_at": 0,
+            "registration_access_token": "token",
+            "registration_client_uri": "https://example.com/register/id",
+            "redirect_uris": ["https://example.com/callback"],
+            "scope": "openid email",
+            "token_endpoint_auth_method": "client_secret_post",
+            "contacts": ["admin@example.com"],
+        }));
+
+        assert_eq!(registration.client_secret.as_deref(), Some("secret"));
+        assert_eq!(registration.client_secret_expires_at, Some(0));
+        assert_eq!(
+            registration.metadata.extra.get("contacts"),
+            Some(&json!(["admin@example.com"]))
+        );
+        assert!(!registration.metadata.extra.contains_key("client_id"));
+
+        let strategy = GenericStrategy::default().set_registration(registration);
+        assert_eq!(strategy.client_id, "id");
+        assert_eq!(strategy.client_secret, "secret");
+        assert_eq!(strategy.redirect_uri, "https://example.com/callback");
+        assert_eq!(strategy.scopes.len(), 2);
+        assert!(matches!(strategy.auth_type, AuthType::RequestBody));
+    }
+
+    #[test]
+    fn registers_public_clients() {
+        let strategy = GenericStrategy::default().set_registration(registration(json!({
+            "client_id": "id",
+            "client_secret": "",
+            "token_endpoint_auth_method": "none",
+        })));
+
+        assert!(strategy.client_secret.is_empty());
+        assert!(matches!(strategy.auth_type, AuthType::RequestBody));
+    }
+
+    #[test]
+    fn keeps_the_management_credentials() {
+        let strategy = GenericStrategy::default()
+            .set_registration(registration(json!({
+                "client_id": "id",
+                "client_secret": "secret",
+                "registration_access_token": "token",
+                "registration_client_uri": "https://example.com/register/id",
+            })))
+            .with_registration(registration(json!({
+                "client_id": "id",
+                "client_name": "renamed",
+            })));
+
+        let registration = strategy.registration().unwrap();
+        assert_eq!(registration.client_secret.as_deref(), Some("secret"));
+        assert_eq!(
+            registration.registration_access_token.as_deref(),
+            Some("token")
+        );
+        assert_eq!(
+            registration.registration_client_uri.as_deref(),
+            Some("https://example.com/register/id")
+        );
+        assert_eq!(
+            registration.metadata.client_name.as_deref(),
+            Some("renamed")
+        );
+        assert_eq!(strategy.client_secret, "secret");
+    }
+
+    #[tokio::test]
+    async fn deletes_with_no_content() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        let strategy = GenericStrategy::default().set_registration(registration(json!({
+            "client_id": "id",
+            "registration_access_token": "token",
+            "registration_client_uri": format!("http://{address}/register/id"),
+        })));
+        strategy.delete_registration().await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("DELETE /register/id "));
+        assert!(request
+            .to_ascii_lowercase()
+            .contains("authorization: bearer token"));
+    }
+}
@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use oauth2::Scope;
+use serde_json::{json, Value};
+
+use super::{Enricher, GithubStrategy, PAccessToken, GITHUB_API_URL};
+use crate::error::Error;
+use crate::passport::Passport;
+
+impl GithubStrategy {
+    /// Only admits the members of at least one of the `orgs`, or of one of the `teams` given as `org/team` slugs.
+    /// The `read:org` scope is requested, and the memberships in the allowed ones are added to the profile under
+    /// `memberships`, as `orgs` and `teams` lists. Other users fail with [`Error::GithubNotMember`]. Calling it again
+    /// replaces the allowed ones.
+    pub fn set_allowed(mut self, orgs: &[&str], teams: &[&str]) -> Self {
+        if !self.scopes.iter().any(|scope| scope.as_str() == "read:org") {
+            self.scopes.push(Scope::new(String::from("read:org")));
+        }
+
+        self.remove_enricher(MEMBERSHIPS_KEY)
+            .add_enricher(Membership {
+                api_url: String::from(GITHUB_API_URL),
+                orgs: orgs.iter().map(|org| org.to_lowercase()).collect(),
+                teams: teams.iter().map(|team| team.to_lowercase()).collect(),
+            })
+    }
+}
+
+/// The profile key of the memberships.
+const MEMBERSHIPS_KEY: &str = "memberships";
+
+/// Checks the memberships of the user in organizations and teams.
+struct Membership {
+    api_url: String,
+    orgs: Vec<String>,
+    teams: Vec<String>,
+}
+
+impl Membership {
+    /// Requests `path` of the Github API, returning `None` when the user is not a member.
+    async fn get(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        path: &str,
+    ) -> Result<Option<Value>, Error> {
+        let response = http
+            .get(format!("{}{path}", self.api_url))
+            .bearer_auth(&access_token.0)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        // Github answers `404 Not Found` to non-members, and `403 Forbidden` when the membership can't be seen,
+        // e.g. the organization restricts access to OAuth apps or the user is outside of it.
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN
+        ) {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        response
+            .json::<Value>()
+            .await
+            .map(Some)
+            .map_err(|error| Error::Reqwest(error.to_string()))
+    }
+}
+
+#[async_trait]
+impl Enricher for Membership {
+    async fn enrich(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error> {
+        let login = match profile["login"].as_str() {
+            Some(login) if !login.is_empty() => login.to_string(),
+            _ => return Err(Error::MissingProfile),
+        };
+
+        // The membership of the user in each allowed organization, rather than the paginated list of all of them.
+        // Pending invitations are not memberships yet.
+        let mut orgs = Vec::new();
+        for org in &self.orgs {
+            let path = format!("/user/memberships/orgs/{org}");
+            if let Some(membership) = self.get(http, access_token, &path).await? {
+                if membership["state"] == "active" {
+                    orgs.push(json!({
+                        "org": membership["organization"]["login"],
+                        "role": membership["role"],
+                    }));
+                }
+            }
+        }
+
+        let mut teams = Vec::new();
+        for slug in &self.teams {
+            let Some((org, team)) = slug.split_once('/') else {
+                continue;
+            };
+            let path = format!("/orgs/{org}/teams/{team}/memberships/{login}");
+            if let Some(membership) = self.get(http, access_token, &path).await? {
+                if membership["state"] == "active" {
+                    teams.push(json!({ "team": slug, "role": membership["role"] }));
+                }
+            }
+        }
+
+        if orgs.is_empty() && teams.is_empty() {
+            return Err(Error::GithubNotMember(login));
+        }
+
+        if let Some(map) = profile.as_object_mut() {
+            map.insert(
+                String::from(MEMBERSHIPS_KEY),
+                json!({ "orgs": orgs, "teams": teams }),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn key(&self) -> Option<&str> {
+        Some(MEMBERSHIPS_KEY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::Strategy;
+    use crate::testing::serve;
+
+    fn membership(url: &str) -> Membership {
+        Membership {
+            api_url: url.to_string(),
+            orgs: vec![String::from("ourco")],
+            teams: vec![String::from("ourco/admins")],
+        }
+    }
+
+    async fn enrich(membership: &Membership) -> Result<Value, Error> {
+        let mut profile = json!({ "login": "ada" });
+        membership
+            .enrich(
+                &reqwest::Client::new(),
+                &PAccessToken(String::from("token")),
+                &mut profile,
+            )
+            .await
+            .map(|_| profile)
+    }
+
+    #[test]
+    fn replaces_the_allowed() {
+        let strategy = GithubStrategy::new("id", "secret", &[], "https://ourco.com/callback")
+            .set_allowed(&["a"], &[])
+            .set_allowed(&["b"], &[]);
+
+        assert_eq!(strategy.enrichers().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn adds_the_active_memberships() {
+        let (url, _) = serve(vec![
+            (
+                "/user/memberships/orgs/ourco",
+                200,
+                json!({ "state": "active", "role": "member", "organization": { "login": "OurCo" } }),
+            ),
+            (
+                "/orgs/ourco/teams/admins/memberships/ada",
+                200,
+                json!({ "state": "pending", "role": "maintainer" }),
+            ),
+        ])
+        .await;
+
+        let profile = enrich(&membership(&url)).await.unwrap();
+        assert_eq!(
+            profile["memberships"],
+            json!({ "orgs": [{ "org": "OurCo", "role": "member" }], "teams": [] })
+        );
+    }
+
+    #[tokio::test]
+    async fn treats_forbidden_as_not_member() {
+        let forbidden =
+            json!({ "message": "Resource protected by organization SAML enforcement." });
+        let (url, _) = serve(vec![
+            ("/user/memberships/orgs/ourco", 403, forbidden.clone()),
+            ("/orgs/ourco/teams/admins/memberships/ada", 403, forbidden),
+        ])
+        .await;
+
+        let error = enrich(&membership(&url)).await.unwrap_err();
+        assert!(matches!(&error, Error::GithubNotMember(login) if login == "ada"));
+        assert_eq!(error.reason(), "not_member");
+    }
+
+    #[tokio::test]
+    async fn admits_a_team_member_forbidden_from_the_org() {
+        let (url, _) = serve(vec![
+            ("/user/memberships/orgs/ourco", 403, json!({})),
+            (
+                "/orgs/ourco/teams/admins/memberships/ada",
+                200,
+                json!({ "state": "active", "role": "member" }),
+            ),
+        ])
+        .await;
+
+        let profile = enrich(&membership(&url)).await.unwrap();
+        assert_eq!(
+            profile["memberships"]["teams"],
+            json!([{ "team": "ourco/admins", "role": "member" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_server_errors() {
+        let (url, _) = serve(vec![("/", 500, json!({ "message": "Server Error" }))]).await;
+
+        assert!(matches!(
+            enrich(&membership(&url)).await,
+            Err(Error::Reqwest(_))
+        ));
+    }
+}
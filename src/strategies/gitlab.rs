@@ -0,0 +1,44 @@
+use oauth2::AuthType;
+
+use super::{
+    GitlabStrategy, TokenExchange, GITLAB_AUTH_PATH, GITLAB_BASE_URL, GITLAB_REQUEST_PATH,
+    GITLAB_TOKEN_PATH, GITLAB_USERINFO_PATH,
+};
+use crate::passport::ResponseMode;
+
+impl Default for GitlabStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: format!("{GITLAB_BASE_URL}{GITLAB_AUTH_PATH}"),
+            scopes: Vec::new(),
+            token_uri: format!("{GITLAB_BASE_URL}{GITLAB_TOKEN_PATH}"),
+            request_uri: format!("{GITLAB_BASE_URL}{GITLAB_REQUEST_PATH}"),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
+            userinfo_uri: Some(format!("{GITLAB_BASE_URL}{GITLAB_USERINFO_PATH}")),
+        }
+    }
+}
+
+impl GitlabStrategy {
+    /// Sets the base url of a self-managed instance, e.g. `https://gitlab.example.com`.
+    /// Defaults to `https://gitlab.com`.
+    pub fn set_base_url(mut self, base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        self.auth_uri = format!("{base_url}{GITLAB_AUTH_PATH}");
+        self.token_uri = format!("{base_url}{GITLAB_TOKEN_PATH}");
+        self.request_uri = format!("{base_url}{GITLAB_REQUEST_PATH}");
+        self.userinfo_uri = Some(format!("{base_url}{GITLAB_USERINFO_PATH}"));
+        self
+    }
+}
@@ -0,0 +1,607 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use oauth2::basic::BasicClient;
+use oauth2::url::{Host, Url};
+use oauth2::{AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope, TokenUrl};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    id_token_claims, pkce_authorize, Strategy, TokenSet, MASTODON_APPS_PATH, MASTODON_AUTH_PATH,
+    MASTODON_CONNECT_TIMEOUT, MASTODON_DEFAULT_INSTANCE, MASTODON_MAX_CLIENTS,
+    MASTODON_REQUEST_PATH, MASTODON_SCOPES, MASTODON_TIMEOUT, MASTODON_TOKEN_PATH,
+};
+use crate::error::Error;
+use crate::passport::{Oauth2ServerResponse, Passport, StateCode};
+
+/// The client credentials of an app registered on a provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Stores the client credentials registered on each instance, e.g. in a database so that the app
+/// is not registered again after a restart.
+#[async_trait]
+pub trait ClientStore: Send + Sync {
+    async fn get(&self, instance: &str) -> Option<ClientCredentials>;
+    async fn set(&self, instance: &str, credentials: ClientCredentials);
+}
+
+/// Keeps the client credentials in memory. This is the default [`ClientStore`].
+///
+/// At most 1000 instances are kept by default, see [`MemoryClientStore::set_capacity`]; the least recently
+/// registered one is evicted to make room, and the app is registered again on its next use.
+#[derive(Clone)]
+pub struct MemoryClientStore {
+    clients: Arc<Mutex<Clients>>,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct Clients {
+    credentials: HashMap<String, ClientCredentials>,
+    /// The instances, the least recently registered first.
+    order: VecDeque<String>,
+}
+
+impl Default for MemoryClientStore {
+    fn default() -> Self {
+        Self {
+            clients: Arc::new(Mutex::new(Clients::default())),
+            capacity: MASTODON_MAX_CLIENTS,
+        }
+    }
+}
+
+impl MemoryClientStore {
+    /// Sets how many instances are kept at most.
+    pub fn set_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl ClientStore for MemoryClientStore {
+    async fn get(&self, instance: &str) -> Option<ClientCredentials> {
+        self.clients
+            .lock()
+            .unwrap()
+            .credentials
+            .get(instance)
+            .cloned()
+    }
+
+    async fn set(&self, instance: &str, credentials: ClientCredentials) {
+        let mut clients = self.clients.lock().unwrap();
+        let clients = &mut *clients;
+        if clients
+            .credentials
+            .insert(instance.to_string(), credentials)
+            .is_none()
+        {
+            clients.order.push_back(instance.to_string());
+        }
+
+        while clients.order.len() > self.capacity {
+            if let Some(oldest) = clients.order.pop_front() {
+                clients.credentials.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Decides whether the app may be registered on an instance, given its domain.
+pub type InstanceValidator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// What is kept until the instance redirects back. The credentials are kept along, since the instance may be
+/// evicted from the [`ClientStore`] in the meantime.
+#[derive(Serialize, Deserialize)]
+struct MastodonSession {
+    instance: String,
+    verifier: String,
+    #[serde(flatten)]
+    credentials: ClientCredentials,
+}
+
+#[derive(Deserialize)]
+struct RegisteredApp {
+    client_id: String,
+    client_secret: String,
+}
+
+/// Mastodon and the Fediverse servers implementing its API.
+///
+/// Every instance is its own OAuth server, so there is no client id and secret to provide: the app is registered
+/// on the user's instance on first use and the credentials are kept in a [`ClientStore`] ([`MemoryClientStore`]
+/// by default). The instance is given at redirect time with
+/// [`Passport::redirect_url_async`](crate::passport::Passport::redirect_url_async), either as a domain
+/// (`mastodon.social`) or a handle (`@user@mastodon.social`).
+/// The `read:accounts` scope is requested when no scopes are given. The profile is the account from
+/// `/api/v1/accounts/verify_credentials`, along with the `instance`.
+///
+/// The instance is chosen by the user, so only public domains on the default port are accepted: IP addresses,
+/// `localhost` and single-label or internal names fail with [`Error::InvalidInstance`]. The domains are not
+/// resolved; restrict them further with [`MastodonStrategy::set_allowed_instances`] or
+/// [`MastodonStrategy::set_instance_validator`]. The requests to the instance time out after 15 seconds, so that
+/// a slow instance can't hold the login.
+#[derive(Clone)]
+pub struct MastodonStrategy {
+    pub(crate) client_name: String,
+    pub(crate) website: Option<String>,
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) redirect_uri: String,
+    pub(crate) store: Arc<dyn ClientStore>,
+    pub(crate) validator: Option<InstanceValidator>,
+    /// The client of the requests to the instances, with timeouts.
+    pub(crate) http: reqwest::Client,
+}
+
+impl MastodonStrategy {
+    /// `client_name` is the name of the app shown to the user by the instance.
+    pub fn new(client_name: &str, scopes: &[&str], redirect_uri: &str) -> Self {
+        let scopes = if scopes.is_empty() {
+            &MASTODON_SCOPES[..]
+        } else {
+            scopes
+        };
+
+        Self {
+            client_name: client_name.to_string(),
+            website: None,
+            scopes: scopes
+                .iter()
+                .map(ToString::to_string)
+                .map(Scope::new)
+                .collect(),
+            redirect_uri: redirect_uri.to_string(),
+            store: Arc::new(MemoryClientStore::default()),
+            validator: None,
+            http: reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(MASTODON_CONNECT_TIMEOUT))
+                .timeout(Duration::from_secs(MASTODON_TIMEOUT))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Sets the website of the app shown to the user by the instance.
+    pub fn set_website(mut self, website: &str) -> Self {
+        self.website = Some(website.to_string());
+        self
+    }
+
+    /// Sets where the client credentials registered on each instance are kept.
+    pub fn set_store<S>(mut self, store: S) -> Self
+    where
+        S: ClientStore + 'static,
+    {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Only admits the given instances, e.g. `&["mastodon.social", "fosstodon.org"]`.
+    pub fn set_allowed_instances(self, instances: &[&str]) -> Self {
+        let instances = instances
+            .iter()
+            .map(|instance| instance.to_lowercase())
+            .collect::<Vec<_>>();
+
+        self.set_instance_validator(move |instance| {
+            instances.iter().any(|allowed| allowed == instance)
+        })
+    }
+
+    /// Only admits the instances the `validator` returns `true` for, given their domain.
+    pub fn set_instance_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// The base url of the user's `instance`, when it is a public domain the validator admits.
+    fn instance(&self, instance: &str) -> Result<Url, Error> {
+        let base = Self::instance_url(instance)?;
+        let domain = base.host_str().unwrap_or_default();
+
+        match &self.validator {
+            Some(validator) if !validator(domain) => {
+                Err(Error::InvalidInstance(instance.to_string()))
+            }
+            _ => Ok(base),
+        }
+    }
+
+    /// Normalizes the `instance`, a domain, url or handle, into the base url of the instance.
+    fn instance_url(instance: &str) -> Result<Url, Error> {
+        let host = instance.trim();
+        let host = host.rsplit('@').next().unwrap_or(host);
+        let host = host
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+            .to_lowercase();
+
+        match Url::parse(&format!("https://{host}")) {
+            Ok(url)
+                if url.path() == "/"
+                    && url.query().is_none()
+                    && url.port().is_none()
+                    && matches!(url.host(), Some(Host::Domain(domain)) if Self::is_public(domain)) =>
+            {
+                Ok(url)
+            }
+            _ => Err(Error::InvalidInstance(instance.to_string())),
+        }
+    }
+
+    /// Whether the `domain` may name a public server, rather than e.g. `localhost` or an intranet host.
+    fn is_public(domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.');
+        let private = [
+            "localhost",
+            "local",
+            "internal",
+            "intranet",
+            "lan",
+            "home",
+            "corp",
+        ];
+
+        match domain.rsplit_once('.') {
+            Some((_, tld)) => !private.contains(&tld) && tld.parse::<u32>().is_err(),
+            None => false,
+        }
+    }
+
+    fn endpoint(base: &Url, path: &str) -> String {
+        format!("{}{}", base.as_str().trim_end_matches('/'), path)
+    }
+
+    /// Returns the client credentials of the instance, registering the app on it on first use.
+    async fn credentials(&self, base: &Url) -> Result<ClientCredentials, Error> {
+        let instance = base.as_str();
+        if let Some(credentials) = self.store.get(instance).await {
+            return Ok(credentials);
+        }
+
+        let scopes = self
+            .scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut params = vec![
+            ("client_name", self.client_name.as_str()),
+            ("redirect_uris", self.redirect_uri.as_str()),
+            ("scopes", scopes.as_str()),
+        ];
+        if let Some(website) = self.website.as_deref() {
+            params.push(("website", website));
+        }
+
+        let response = self
+            .http
+            .post(Self::endpoint(base, MASTODON_APPS_PATH))
+            .form(&params)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        let app = response
+            .json::<RegisteredApp>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))?;
+        let credentials = ClientCredentials {
+            client_id: app.client_id,
+            client_secret: app.client_secret,
+        };
+        self.store.set(instance, credentials.clone()).await;
+
+        Ok(credentials)
+    }
+
+    fn client(&self, base: &Url, credentials: ClientCredentials) -> Result<BasicClient, Error> {
+        Ok(BasicClient::new(
+            ClientId::new(credentials.client_id),
+            Some(ClientSecret::new(credentials.client_secret)),
+            AuthUrl::new(Self::endpoint(base, MASTODON_AUTH_PATH))?,
+            Some(TokenUrl::new(Self::endpoint(base, MASTODON_TOKEN_PATH))?),
+        )
+        .set_redirect_uri(RedirectUrl::new(self.redirect_uri.clone())?))
+    }
+}
+
+#[async_trait]
+impl Strategy for MastodonStrategy {
+    fn request_uri(&self) -> String {
+        format!("https://{MASTODON_DEFAULT_INSTANCE}{MASTODON_REQUEST_PATH}")
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        self.scopes.clone()
+    }
+
+    fn client_id(&self) -> String {
+        String::new()
+    }
+
+    fn client_secret(&self) -> String {
+        String::new()
+    }
+
+    fn auth_url(&self) -> String {
+        format!("https://{MASTODON_DEFAULT_INSTANCE}{MASTODON_AUTH_PATH}")
+    }
+
+    fn redirect_url(&self) -> String {
+        self.redirect_uri.clone()
+    }
+
+    fn token_url(&self) -> Result<TokenUrl, Error> {
+        match TokenUrl::new(format!(
+            "https://{MASTODON_DEFAULT_INSTANCE}{MASTODON_TOKEN_PATH}"
+        )) {
+            Ok(token) => Ok(token),
+            Err(err) => Err(Error::ParseError(err)),
+        }
+    }
+
+    fn authorize(&self, _client: &BasicClient) -> Result<(Url, CsrfToken, String), Error> {
+        Err(Error::AsyncAuthorizationRequired)
+    }
+
+    async fn authorize_async(
+        &self,
+        _client: &BasicClient,
+        instance: Option<&str>,
+    ) -> Result<(Url, CsrfToken, String), Error> {
+        let base = self.instance(instance.unwrap_or_default())?;
+        let credentials = self.credentials(&base).await?;
+        let (auth_url, csrf_token, verifier) =
+            pkce_authorize(self, &self.client(&base, credentials.clone())?);
+
+        let session = MastodonSession {
+            instance: base.to_string(),
+            verifier,
+            credentials,
+        };
+        let secret = serde_json::to_string(&session)?;
+
+        Ok((auth_url, csrf_token, secret))
+    }
+
+    async fn callback(
+        &self,
+        _client: &BasicClient,
+        secret: String,
+        statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let code = statecode
+            .code
+            .clone()
+            .ok_or(Error::MissingAuthorizationCode)?;
+        let session = serde_json::from_str::<MastodonSession>(&secret)
+            .map_err(|_| Error::InvalidInstance(String::new()))?;
+        let base = Self::instance_url(&session.instance)?;
+
+        self.authenticate(&base, &session, &code).await
+    }
+}
+
+impl MastodonStrategy {
+    /// Exchanges the `code` on the instance at `base` with the credentials of the `session`, and requests the
+    /// account.
+    async fn authenticate(
+        &self,
+        base: &Url,
+        session: &MastodonSession,
+        code: &str,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let response = self
+            .http
+            .post(Self::endpoint(base, MASTODON_TOKEN_PATH))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+                ("client_id", &session.credentials.client_id),
+                ("client_secret", &session.credentials.client_secret),
+                ("code_verifier", &session.verifier),
+            ])
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+        let tokens = response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::InvalidResponse(error.to_string()))
+            .and_then(TokenSet::try_from)?;
+
+        let response = self
+            .http
+            .get(Self::endpoint(base, MASTODON_REQUEST_PATH))
+            .bearer_auth(&tokens.access_token.0)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+        let mut profile = response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::InvalidResponse(error.to_string()))?;
+        if let Some(map) = profile.as_object_mut() {
+            map.insert(
+                String::from("instance"),
+                Value::String(base.host_str().unwrap_or_default().to_string()),
+            );
+
+            if let Some(claims) = tokens.extra["id_token"].as_str().and_then(id_token_claims) {
+                map.insert(String::from("id_token_claims"), claims);
+            }
+        }
+
+        Ok(Oauth2ServerResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            token_secret: None,
+            profile,
+            roles: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::serve;
+
+    fn strategy() -> MastodonStrategy {
+        MastodonStrategy::new("app", &[], "https://example.com/callback")
+    }
+
+    #[test]
+    fn accepts_domains_and_handles() {
+        let strategy = strategy();
+
+        for instance in [
+            "mastodon.social",
+            "@user@Mastodon.Social",
+            "https://mastodon.social/",
+        ] {
+            let base = strategy.instance(instance).unwrap();
+            assert_eq!(base.as_str(), "https://mastodon.social/");
+        }
+    }
+
+    #[test]
+    fn rejects_private_hosts() {
+        let strategy = strategy();
+
+        for instance in [
+            "",
+            "localhost",
+            "app.localhost",
+            "127.0.0.1",
+            "[::1]",
+            "10.0.0.1",
+            "0x7f000001",
+            "169.254.169.254",
+            "metadata",
+            "printer.local",
+            "mastodon.social:8080",
+            "mastodon.social/path",
+            "user:pass@mastodon.social@evil.internal",
+        ] {
+            assert!(
+                matches!(strategy.instance(instance), Err(Error::InvalidInstance(_))),
+                "{instance} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn applies_the_allowlist() {
+        let strategy = strategy().set_allowed_instances(&["Fosstodon.org"]);
+
+        assert!(strategy.instance("@user@fosstodon.org").is_ok());
+        assert!(strategy.instance("mastodon.social").is_err());
+    }
+
+    #[tokio::test]
+    async fn bounds_the_memory_store() {
+        let store = MemoryClientStore::default().set_capacity(2);
+        let credentials = ClientCredentials {
+            client_id: String::from("id"),
+            client_secret: String::from("secret"),
+        };
+
+        for instance in ["a.example", "b.example", "c.example"] {
+            store.set(instance, credentials.clone()).await;
+        }
+
+        assert!(store.get("a.example").await.is_none());
+        assert!(store.get("b.example").await.is_some());
+        assert!(store.get("c.example").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn authenticates_with_the_credentials_of_the_session() {
+        let (url, requests) = serve(vec![
+            (
+                MASTODON_TOKEN_PATH,
+                200,
+                json!({ "access_token": "access", "token_type": "Bearer" }),
+            ),
+            (
+                MASTODON_REQUEST_PATH,
+                200,
+                json!({ "id": "1", "username": "ada" }),
+            ),
+        ])
+        .await;
+        let base = Url::parse(&url).unwrap();
+        // The instance was evicted from the store since the redirect.
+        let strategy = strategy().set_store(MemoryClientStore::default());
+        let session = MastodonSession {
+            instance: base.to_string(),
+            verifier: String::from("verifier"),
+            credentials: ClientCredentials {
+                client_id: String::from("id"),
+                client_secret: String::from("secret"),
+            },
+        };
+
+        let response = strategy
+            .authenticate(&base, &session, "code")
+            .await
+            .unwrap();
+        assert_eq!(response.access_token.0, "access");
+        assert_eq!(response.profile["username"], "ada");
+        assert_eq!(response.profile["instance"], "127.0.0.1");
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("client_id=id&client_secret=secret&code_verifier=verifier"));
+        assert!(requests[1]
+            .to_ascii_lowercase()
+            .contains("authorization: bearer access"));
+    }
+
+    #[test]
+    fn keeps_the_credentials_in_the_session() {
+        let session = MastodonSession {
+            instance: String::from("https://mastodon.social/"),
+            verifier: String::from("verifier"),
+            credentials: ClientCredentials {
+                client_id: String::from("id"),
+                client_secret: String::from("secret"),
+            },
+        };
+
+        let secret = serde_json::to_string(&session).unwrap();
+        let session = serde_json::from_str::<MastodonSession>(&secret).unwrap();
+        assert_eq!(session.credentials.client_id, "id");
+        assert_eq!(session.credentials.client_secret, "secret");
+    }
+}
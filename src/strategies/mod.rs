@@ -1,5 +1,27 @@
+mod apple;
+mod atlassian;
 mod constants;
+mod discord;
+mod enrich;
+mod generic;
+mod github;
+mod gitlab;
+mod mastodon;
+mod oauth1;
+mod slack;
+mod steam;
+#[allow(clippy::module_inception)]
 mod strategies;
+mod telegram;
+mod twitch;
+mod twitter;
 
+pub use apple::*;
 use constants::*;
+pub use enrich::*;
+pub use generic::*;
+pub use mastodon::*;
+pub use oauth1::*;
+pub use steam::*;
 pub use strategies::*;
+pub use telegram::*;
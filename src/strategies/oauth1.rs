@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use oauth2::basic::BasicClient;
+use oauth2::url::{form_urlencoded, Url};
+use oauth2::{CsrfToken, Scope, TokenUrl};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::Sha1;
+
+use super::{PAccessToken, PTokenSecret, Strategy};
+use crate::error::Error;
+use crate::passport::{now, Oauth2ServerResponse, Passport, StateCode};
+
+/// Everything but the unreserved characters is percent-encoded when signing
+/// ([RFC 5849](https://www.rfc-editor.org/rfc/rfc5849#section-3.6)).
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, UNRESERVED).to_string()
+}
+
+/// Signs a request with HMAC-SHA1 ([RFC 5849](https://www.rfc-editor.org/rfc/rfc5849#section-3.4)). The query
+/// parameters of the `url` are signed along with the `oauth_*` ones.
+fn signature(
+    method: &str,
+    url: &Url,
+    oauth: &[(&str, &str)],
+    consumer_secret: &str,
+    token_secret: &str,
+) -> Result<String, Error> {
+    let mut params = oauth
+        .iter()
+        .map(|(name, value)| (encode(name), encode(value)))
+        .chain(
+            url.query_pairs()
+                .map(|(name, value)| (encode(&name), encode(&value))),
+        )
+        .collect::<Vec<_>>();
+    params.sort();
+    let params = params
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let mut base_url = url.clone();
+    base_url.set_query(None);
+    base_url.set_fragment(None);
+    let base = format!(
+        "{}&{}&{}",
+        method,
+        encode(base_url.as_str()),
+        encode(&params)
+    );
+
+    let key = format!("{}&{}", encode(consumer_secret), encode(token_secret));
+    let mut mac = Hmac::<Sha1>::new_from_slice(key.as_bytes())
+        .map_err(|error| Error::InvalidKey(error.to_string()))?;
+    mac.update(base.as_bytes());
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+/// The request token kept until the provider redirects back, like the PKCE verifier of the OAuth 2.0 strategies.
+#[derive(Serialize, Deserialize)]
+struct Oauth1Session {
+    token: String,
+    token_secret: String,
+}
+
+/// Any OAuth 1.0a provider, e.g. Tumblr, Trello or Garmin Connect, given its request token, authorization and
+/// access token endpoints. Requests are signed with HMAC-SHA1.
+///
+/// The request token is fetched before redirecting the user, so the redirect url is built with
+/// [`Passport::redirect_url_async`](crate::passport::Passport::redirect_url_async). The access token and its
+/// secret are returned in the [`Oauth2ServerResponse`]. The profile is requested at the endpoint set with
+/// [`Oauth1Strategy::set_request_uri`], or is the rest of the access token response (e.g. `user_id` and
+/// `screen_name`) when there is none.
+#[derive(Clone)]
+pub struct Oauth1Strategy {
+    pub(crate) consumer_key: String,
+    pub(crate) consumer_secret: String,
+    pub(crate) request_token_uri: String,
+    pub(crate) auth_uri: String,
+    pub(crate) token_uri: String,
+    pub(crate) request_uri: String,
+    pub(crate) redirect_uri: String,
+    pub(crate) auth_params: Vec<(String, String)>,
+}
+
+impl Oauth1Strategy {
+    pub fn new(
+        consumer_key: &str,
+        consumer_secret: &str,
+        request_token_url: &str,
+        auth_url: &str,
+        token_url: &str,
+        redirect_uri: &str,
+    ) -> Self {
+        Self {
+            consumer_key: consumer_key.to_string(),
+            consumer_secret: consumer_secret.to_string(),
+            request_token_uri: request_token_url.to_string(),
+            auth_uri: auth_url.to_string(),
+            token_uri: token_url.to_string(),
+            request_uri: String::new(),
+            redirect_uri: redirect_uri.to_string(),
+            auth_params: Vec::new(),
+        }
+    }
+
+    /// Sets the profile endpoint of the provider, e.g. `https://api.trello.com/1/members/me`.
+    pub fn set_request_uri(mut self, request_uri: &str) -> Self {
+        self.request_uri = request_uri.to_string();
+        self
+    }
+
+    /// Sets extra parameters added to the authorization url, e.g. `scope` and `expiration` for Trello.
+    pub fn set_auth_params(mut self, params: &[(&str, &str)]) -> Self {
+        self.auth_params = params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self
+    }
+
+    /// Builds the `Authorization` header of a request signed with the consumer secret and the `token`, if any.
+    /// `oauth_params` are the extra `oauth_*` parameters e.g. `oauth_callback`.
+    fn authorization(
+        &self,
+        method: &str,
+        url: &str,
+        token: Option<(&str, &str)>,
+        oauth_params: &[(&str, &str)],
+    ) -> Result<String, Error> {
+        let url = Url::parse(url)?;
+        let timestamp = now().to_string();
+        let nonce = CsrfToken::new_random().secret().clone();
+
+        let mut oauth = vec![
+            ("oauth_consumer_key", self.consumer_key.as_str()),
+            ("oauth_nonce", nonce.as_str()),
+            ("oauth_signature_method", "HMAC-SHA1"),
+            ("oauth_timestamp", timestamp.as_str()),
+            ("oauth_version", "1.0"),
+        ];
+        if let Some((token, _)) = token {
+            oauth.push(("oauth_token", token));
+        }
+        oauth.extend_from_slice(oauth_params);
+
+        let token_secret = token.map(|(_, secret)| secret).unwrap_or_default();
+        let signature = signature(method, &url, &oauth, &self.consumer_secret, token_secret)?;
+
+        oauth.push(("oauth_signature", signature.as_str()));
+        let header = oauth
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", encode(name), encode(value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Ok(format!("OAuth {header}"))
+    }
+
+    /// Requests a token at `url` and returns the form encoded response.
+    async fn token_request(
+        &self,
+        url: &str,
+        token: Option<(&str, &str)>,
+        oauth_params: &[(&str, &str)],
+    ) -> Result<HashMap<String, String>, Error> {
+        let response = reqwest::Client::new()
+            .post(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                self.authorization("POST", url, token, oauth_params)?,
+            )
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        let body = response
+            .text()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))?;
+
+        Ok(form_urlencoded::parse(body.as_bytes())
+            .into_owned()
+            .collect())
+    }
+
+    /// Requests the profile at the `request_uri` with the access `token`.
+    async fn request_profile(&self, token: (&str, &str)) -> Result<Value, Error> {
+        let response = reqwest::Client::new()
+            .get(&self.request_uri)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                self.authorization("GET", &self.request_uri, Some(token), &[])?,
+            )
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if response.status().is_success() {
+            response
+                .json::<Value>()
+                .await
+                .map_err(|error| Error::Reqwest(error.to_string()))
+        } else {
+            Err(Error::Reqwest(response.text().await.unwrap_or_default()))
+        }
+    }
+}
+
+#[async_trait]
+impl Strategy for Oauth1Strategy {
+    fn request_uri(&self) -> String {
+        self.request_uri.clone()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        Vec::new()
+    }
+
+    fn client_id(&self) -> String {
+        self.consumer_key.clone()
+    }
+
+    fn client_secret(&self) -> String {
+        self.consumer_secret.clone()
+    }
+
+    fn auth_url(&self) -> String {
+        self.auth_uri.clone()
+    }
+
+    fn redirect_url(&self) -> String {
+        self.redirect_uri.clone()
+    }
+
+    fn auth_params(&self) -> Vec<(String, String)> {
+        self.auth_params.clone()
+    }
+
+    fn token_url(&self) -> Result<TokenUrl, Error> {
+        match TokenUrl::new(self.token_uri.clone()) {
+            Ok(token) => Ok(token),
+            Err(err) => Err(Error::ParseError(err)),
+        }
+    }
+
+    fn authorize(&self, _client: &BasicClient) -> Result<(Url, CsrfToken, String), Error> {
+        Err(Error::AsyncAuthorizationRequired)
+    }
+
+    async fn authorize_async(
+        &self,
+        client: &BasicClient,
+        _instance: Option<&str>,
+    ) -> Result<(Url, CsrfToken, String), Error> {
+        // The provider redirects back with the `oauth_token`, the state is carried by the callback url.
+        let csrf_token = CsrfToken::new_random();
+        let mut callback = Url::parse(&self.redirect_uri)?;
+        callback
+            .query_pairs_mut()
+            .append_pair("state", csrf_token.secret());
+
+        let mut response = self
+            .token_request(
+                &self.request_token_uri,
+                None,
+                &[("oauth_callback", callback.as_str())],
+            )
+            .await?;
+        let session = match (
+            response.remove("oauth_token"),
+            response.remove("oauth_token_secret"),
+        ) {
+            (Some(token), Some(token_secret)) => Oauth1Session {
+                token,
+                token_secret,
+            },
+            _ => return Err(Error::MissingAccessToken),
+        };
+
+        let mut auth_url = client.auth_url().url().clone();
+        auth_url
+            .query_pairs_mut()
+            .append_pair("oauth_token", &session.token)
+            .extend_pairs(self.auth_params());
+
+        let secret = serde_json::to_string(&session)?;
+
+        Ok((auth_url, csrf_token, secret))
+    }
+
+    async fn callback(
+        &self,
+        _client: &BasicClient,
+        secret: String,
+        statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let session = serde_json::from_str::<Oauth1Session>(&secret)?;
+
+        if statecode.params.get("oauth_token") != Some(&session.token) {
+            return Err(Error::CSRFTokenMismatch);
+        }
+        let verifier = statecode
+            .params
+            .get("oauth_verifier")
+            .ok_or(Error::MissingAuthorizationCode)?;
+
+        let mut response = self
+            .token_request(
+                &self.token_uri,
+                Some((&session.token, &session.token_secret)),
+                &[("oauth_verifier", verifier)],
+            )
+            .await?;
+        let (token, token_secret) = match (
+            response.remove("oauth_token"),
+            response.remove("oauth_token_secret"),
+        ) {
+            (Some(token), Some(token_secret)) => (token, token_secret),
+            _ => return Err(Error::MissingAccessToken),
+        };
+
+        let profile = if self.request_uri.is_empty() {
+            serde_json::to_value(response)?
+        } else {
+            self.request_profile((&token, &token_secret)).await?
+        };
+
+        Ok(Oauth2ServerResponse {
+            access_token: PAccessToken(token),
+            refresh_token: None,
+            token_secret: Some(PTokenSecret(token_secret)),
+            profile,
+            roles: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example of [RFC 5849 section 1.2](https://www.rfc-editor.org/rfc/rfc5849#section-1.2).
+    #[test]
+    fn signs_the_rfc_5849_example() {
+        let url =
+            Url::parse("http://photos.example.net/photos?file=vacation.jpg&size=original").unwrap();
+        let oauth = [
+            ("oauth_consumer_key", "dpf43f3p2l4k3l03"),
+            ("oauth_token", "nnch734d00sl2jdk"),
+            ("oauth_signature_method", "HMAC-SHA1"),
+            ("oauth_timestamp", "137131202"),
+            ("oauth_nonce", "chapoH"),
+        ];
+
+        let signature =
+            signature("GET", &url, &oauth, "kd94hf93k423kf44", "pfkkdhi9sl3r4s00").unwrap();
+
+        assert_eq!(signature, "MdpQcU8iPSUjWoN/UDMsK2sui9I=");
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use oauth2::{AuthType, Scope};
+use serde_json::Value;
+
+use super::{
+    Enricher, PAccessToken, SlackStrategy, TokenExchange, SLACK_AUTH_URL, SLACK_REQUEST_URL,
+    SLACK_SCOPES, SLACK_TEAM_ID_CLAIM, SLACK_TOKEN_URL,
+};
+use crate::error::Error;
+use crate::passport::ResponseMode;
+
+impl Default for SlackStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::from(SLACK_AUTH_URL),
+            scopes: SLACK_SCOPES
+                .iter()
+                .map(ToString::to_string)
+                .map(Scope::new)
+                .collect(),
+            token_uri: String::from(SLACK_TOKEN_URL),
+            request_uri: String::from(SLACK_REQUEST_URL),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            // The token endpoint answers failures with `200 OK` and `{"ok": false}`, which only the direct
+            // token request checks for.
+            token_exchange: TokenExchange::RequestBody,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: true,
+            userinfo_uri: None,
+            client_id_header: None,
+            auth_params: Vec::new(),
+        }
+    }
+}
+
+impl SlackStrategy {
+    /// Pins the login to the workspace with the given team id, e.g. `T0123ABCD`.
+    pub fn set_team(mut self, team: &str) -> Self {
+        self.auth_params.retain(|(name, _)| name != "team");
+        self.auth_params
+            .push((String::from("team"), team.to_string()));
+
+        // The workspace check is kept first among the enrichers, replacing the one of a previous call.
+        self.enrichers
+            .retain(|enricher| enricher.key() != Some(SLACK_TEAM_KEY));
+        self.enrichers.insert(
+            0,
+            Arc::new(Team {
+                team: team.to_string(),
+            }),
+        );
+        self
+    }
+}
+
+/// The key of the workspace check among the enrichers.
+const SLACK_TEAM_KEY: &str = "team";
+
+/// Checks the workspace the user signed in to.
+struct Team {
+    team: String,
+}
+
+#[async_trait]
+impl Enricher for Team {
+    async fn enrich(
+        &self,
+        _http: &reqwest::Client,
+        _access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error> {
+        let team_id = profile[SLACK_TEAM_ID_CLAIM].as_str().unwrap_or_default();
+        if team_id != self.team {
+            return Err(Error::SlackTeamMismatch(team_id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn key(&self) -> Option<&str> {
+        Some(SLACK_TEAM_KEY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::{GithubStrategy, Strategy};
+    use crate::testing::serve;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn replaces_the_pinned_team() {
+        let strategy = SlackStrategy::new("app", "secret", &[], "https://example.com/callback")
+            .set_team("A")
+            .set_team("B");
+
+        assert_eq!(
+            strategy.auth_params,
+            vec![(String::from("team"), String::from("B"))]
+        );
+        assert_eq!(strategy.enrichers.len(), 1);
+
+        let http = reqwest::Client::new();
+        let access_token = PAccessToken(String::from("token"));
+        let mut profile = json!({ SLACK_TEAM_ID_CLAIM: "B" });
+        assert!(strategy.enrichers[0]
+            .enrich(&http, &access_token, &mut profile)
+            .await
+            .is_ok());
+
+        let mut profile = json!({ SLACK_TEAM_ID_CLAIM: "A" });
+        assert!(matches!(
+            strategy.enrichers[0]
+                .enrich(&http, &access_token, &mut profile)
+                .await,
+            Err(Error::SlackTeamMismatch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_failures_answered_with_ok() {
+        let failure = json!({ "ok": false, "error": "invalid_auth" });
+        let (url, _) = serve(vec![("/", 200, failure.clone())]).await;
+        let access_token = PAccessToken(String::from("token"));
+
+        let mut slack = SlackStrategy::new("app", "secret", &[], "https://example.com/callback");
+        slack.request_uri = format!("{url}/api/users.identity");
+        slack.userinfo_uri = None;
+        assert!(matches!(
+            slack.profile(&access_token).await,
+            Err(Error::Reqwest(error)) if error == "invalid_auth"
+        ));
+
+        // Other providers may have an `ok` field of their own.
+        let mut github = GithubStrategy::new("app", "secret", &[], "https://example.com/callback");
+        github.request_uri = format!("{url}/user");
+        assert_eq!(github.profile(&access_token).await.unwrap(), failure);
+    }
+}
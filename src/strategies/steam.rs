@@ -0,0 +1,313 @@
+use async_trait::async_trait;
+use oauth2::basic::BasicClient;
+use oauth2::url::Url;
+use oauth2::{CsrfToken, Scope, TokenUrl};
+use serde_json::{json, Value};
+
+use super::{
+    PAccessToken, Strategy, OPENID_IDENTIFIER_SELECT, OPENID_NS, STEAM_CLAIMED_ID_PREFIX,
+    STEAM_OPENID_URL, STEAM_REQUEST_URL,
+};
+use crate::error::Error;
+use crate::passport::{Oauth2ServerResponse, Passport, StateCode};
+
+/// Steam, which signs users in with OpenID 2.0 rather than OAuth 2.0.
+///
+/// The `state` is carried in the `openid.return_to` url, and the assertion Steam redirects back with is verified
+/// with a `check_authentication` request to Steam. The profile holds the user's `steamid` (SteamID64), and
+/// the player summary from `ISteamUser/GetPlayerSummaries` when a Web API key is set with
+/// [`SteamStrategy::set_api_key`]. There are no tokens, so the `access_token` of the response is empty.
+#[derive(Clone)]
+pub struct SteamStrategy {
+    pub(crate) redirect_uri: String,
+    pub(crate) api_key: Option<String>,
+}
+
+impl SteamStrategy {
+    pub fn new(redirect_uri: &str) -> Self {
+        Self {
+            redirect_uri: redirect_uri.to_string(),
+            api_key: None,
+        }
+    }
+
+    /// Sets the Steam Web API key used to retrieve the player summary.
+    pub fn set_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Checks the `openid.*` parameters of the assertion before it is verified by Steam: the signed `return_to`
+    /// must be the redirect url carrying the `state` of the `statecode`.
+    fn check_assertion(&self, statecode: &StateCode) -> Result<(), Error> {
+        let param = |name: &str| {
+            statecode
+                .params
+                .get(name)
+                .map(String::as_str)
+                .unwrap_or_default()
+        };
+
+        if param("openid.mode") != "id_res" {
+            return Err(Error::InvalidOpenIdAssertion(format!(
+                "unexpected mode `{}`",
+                param("openid.mode")
+            )));
+        }
+
+        if param("openid.op_endpoint") != STEAM_OPENID_URL {
+            return Err(Error::InvalidOpenIdAssertion(String::from(
+                "unexpected op_endpoint",
+            )));
+        }
+
+        let signed = param("openid.signed").split(',').collect::<Vec<_>>();
+        if let Some(field) = ["claimed_id", "identity", "return_to", "op_endpoint"]
+            .into_iter()
+            .find(|field| !signed.contains(field))
+        {
+            return Err(Error::InvalidOpenIdAssertion(format!(
+                "`{field}` is not signed"
+            )));
+        }
+
+        let redirect_uri = Url::parse(&self.redirect_uri)?;
+        let return_to = Url::parse(param("openid.return_to"))
+            .ok()
+            .filter(|return_to| {
+                return_to.origin() == redirect_uri.origin()
+                    && return_to.path() == redirect_uri.path()
+            })
+            .ok_or_else(|| Error::InvalidOpenIdAssertion(String::from("unexpected return_to")))?;
+
+        let state = return_to
+            .query_pairs()
+            .find(|(name, _)| name == "state")
+            .map(|(_, state)| state.into_owned());
+        match (state, statecode.state.as_ref()) {
+            (Some(state), Some(expected)) if &state == expected.secret() => Ok(()),
+            _ => Err(Error::InvalidOpenIdAssertion(String::from(
+                "unexpected state in return_to",
+            ))),
+        }
+    }
+
+    /// Verifies the assertion in the `openid.*` parameters and returns the SteamID64.
+    async fn verify(&self, statecode: &StateCode) -> Result<String, Error> {
+        self.check_assertion(statecode)?;
+
+        let mut params = statecode
+            .params
+            .iter()
+            .filter(|(name, _)| name.starts_with("openid."))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        for (name, value) in params.iter_mut() {
+            if *name == "openid.mode" {
+                *value = "check_authentication";
+            }
+        }
+
+        let response = reqwest::Client::new()
+            .post(STEAM_OPENID_URL)
+            .form(&params)
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if !response.lines().any(|line| line.trim() == "is_valid:true") {
+            return Err(Error::InvalidOpenIdAssertion(String::from(
+                "rejected by check_authentication",
+            )));
+        }
+
+        statecode
+            .params
+            .get("openid.claimed_id")
+            .and_then(|claimed_id| claimed_id.strip_prefix(STEAM_CLAIMED_ID_PREFIX))
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+            .map(ToString::to_string)
+            .ok_or_else(|| Error::InvalidOpenIdAssertion(String::from("unexpected claimed_id")))
+    }
+
+    /// Requests the player summary of `steam_id` with the Web API key.
+    async fn player_summary(&self, api_key: &str, steam_id: &str) -> Result<Value, Error> {
+        let response = reqwest::Client::new()
+            .get(STEAM_REQUEST_URL)
+            .query(&[("key", api_key), ("steamids", steam_id)])
+            .header(reqwest::header::USER_AGENT, Passport::USER_AGENT)
+            .send()
+            .await
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Reqwest(response.text().await.unwrap_or_default()));
+        }
+
+        response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))?
+            .pointer_mut("/response/players/0")
+            .map(Value::take)
+            .ok_or(Error::MissingProfile)
+    }
+}
+
+#[async_trait]
+impl Strategy for SteamStrategy {
+    fn request_uri(&self) -> String {
+        String::from(STEAM_REQUEST_URL)
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        Vec::new()
+    }
+
+    fn client_id(&self) -> String {
+        String::new()
+    }
+
+    fn client_secret(&self) -> String {
+        String::new()
+    }
+
+    fn auth_url(&self) -> String {
+        String::from(STEAM_OPENID_URL)
+    }
+
+    fn redirect_url(&self) -> String {
+        self.redirect_uri.clone()
+    }
+
+    /// Assertions are verified at the OpenID endpoint.
+    fn token_url(&self) -> Result<TokenUrl, Error> {
+        match TokenUrl::new(String::from(STEAM_OPENID_URL)) {
+            Ok(token) => Ok(token),
+            Err(err) => Err(Error::ParseError(err)),
+        }
+    }
+
+    fn authorize(&self, client: &BasicClient) -> Result<(Url, CsrfToken, String), Error> {
+        let csrf_token = CsrfToken::new_random();
+
+        let mut return_to = Url::parse(&self.redirect_uri)?;
+        return_to
+            .query_pairs_mut()
+            .append_pair("state", csrf_token.secret());
+        let realm = format!("{}/", return_to.origin().ascii_serialization());
+
+        let mut auth_url = client.auth_url().url().clone();
+        auth_url.query_pairs_mut().extend_pairs([
+            ("openid.ns", OPENID_NS),
+            ("openid.mode", "checkid_setup"),
+            ("openid.return_to", return_to.as_str()),
+            ("openid.realm", &realm),
+            ("openid.identity", OPENID_IDENTIFIER_SELECT),
+            ("openid.claimed_id", OPENID_IDENTIFIER_SELECT),
+        ]);
+
+        Ok((auth_url, csrf_token, String::new()))
+    }
+
+    async fn callback(
+        &self,
+        _client: &BasicClient,
+        _secret: String,
+        statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let steam_id = self.verify(statecode).await?;
+
+        let profile = match self.api_key.as_deref() {
+            Some(api_key) => self.player_summary(api_key, &steam_id).await?,
+            None => json!({ "steamid": steam_id }),
+        };
+
+        Ok(Oauth2ServerResponse {
+            access_token: PAccessToken(String::new()),
+            refresh_token: None,
+            token_secret: None,
+            profile,
+            roles: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statecode(return_to: &str, signed: &str) -> StateCode {
+        let params = [
+            ("openid.mode", "id_res"),
+            ("openid.op_endpoint", STEAM_OPENID_URL),
+            ("openid.signed", signed),
+            ("openid.return_to", return_to),
+        ];
+
+        StateCode {
+            state: Some(CsrfToken::new(String::from("abc"))),
+            code: None,
+            user: None,
+            params: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    const SIGNED: &str =
+        "signed,op_endpoint,claimed_id,identity,return_to,response_nonce,assoc_handle";
+
+    #[test]
+    fn accepts_the_redirect_url() {
+        let strategy = SteamStrategy::new("https://a.com/auth/steam/callback");
+
+        assert!(strategy
+            .check_assertion(&statecode(
+                "https://a.com/auth/steam/callback?state=abc",
+                SIGNED
+            ))
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_other_return_to() {
+        let strategy = SteamStrategy::new("https://a.com");
+
+        for return_to in [
+            "https://a.com.evil.com/?state=abc",
+            "https://a.com@evil.com/?state=abc",
+            "http://a.com/?state=abc",
+            "https://a.com:8443/?state=abc",
+            "https://a.com/other?state=abc",
+            "https://a.com/?state=xyz",
+            "https://a.com/",
+            "not a url",
+        ] {
+            assert!(
+                matches!(
+                    strategy.check_assertion(&statecode(return_to, SIGNED)),
+                    Err(Error::InvalidOpenIdAssertion(_))
+                ),
+                "{return_to} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn requires_the_signed_fields() {
+        let strategy = SteamStrategy::new("https://a.com/callback");
+
+        assert!(strategy
+            .check_assertion(&statecode(
+                "https://a.com/callback?state=abc",
+                "op_endpoint,identity,return_to"
+            ))
+            .is_err());
+    }
+}
@@ -1,26 +1,45 @@
+use std::collections::HashMap;
 use std::fmt::{Debug, Display};
+use std::sync::Arc;
 
 use super::{
-    DISCORD_AUTH_URL, DISCORD_REQUEST_URL, DISCORD_TOKEN_URL, FACEBOOK_AUTH_URL,
+    Enricher, DISCORD_AUTH_URL, DISCORD_REQUEST_URL, DISCORD_TOKEN_URL, FACEBOOK_AUTH_URL,
     FACEBOOK_REQUEST_URL, FACEBOOK_TOKEN_URL, FORTYTWO_AUTH_URL, FORTYTWO_REQUEST_URL,
     FORTYTWO_TOKEN_URL, GITHUB_AUTH_URL, GITHUB_REQUEST_URL, GITHUB_TOKEN_URL, GOOGLE_AUTH_URL,
-    GOOGLE_REQUEST_URL, GOOGLE_TOKEN_URL, MICROSOFT_AUTH_URL, MICROSOFT_REQUEST_URL,
-    MICROSOFT_TOKEN_URL, REDDIT_AUTH_URL, REDDIT_REQUEST_URL, REDDIT_TOKEN_URL,
+    GOOGLE_REQUEST_URL, GOOGLE_TOKEN_URL, LINKEDIN_AUTH_URL, LINKEDIN_REQUEST_URL, LINKEDIN_SCOPES,
+    LINKEDIN_TOKEN_URL, MICROSOFT_AUTH_URL, MICROSOFT_REQUEST_URL, MICROSOFT_TOKEN_URL,
+    REDDIT_AUTH_URL, REDDIT_REQUEST_URL, REDDIT_TOKEN_URL,
 };
-use oauth2::{Scope, TokenUrl};
+use async_trait::async_trait;
+use base64::Engine;
+use oauth2::basic::{
+    BasicClient, BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenType,
+};
+use oauth2::reqwest::async_http_client;
+use oauth2::url::Url;
+use oauth2::{
+    AuthType, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, ExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, StandardRevocableToken,
+    StandardTokenResponse, TokenUrl,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::passport::{Oauth2ServerResponse, Passport, ResponseMode, StateCode};
 
 #[derive(Clone)]
 pub struct PAccessToken(pub String);
 
 impl Display for PAccessToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", "PAccessToken([Redacted])")
+        write!(f, "PAccessToken([Redacted])")
     }
 }
 
 impl Debug for PAccessToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", "PAccessToken([Redacted])")
+        write!(f, "PAccessToken([Redacted])")
     }
 }
 
@@ -29,13 +48,28 @@ pub struct PRefreshToken(pub String);
 
 impl Display for PRefreshToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", "PRefreshToken([Redacted])")
+        write!(f, "PRefreshToken([Redacted])")
     }
 }
 
 impl Debug for PRefreshToken {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", "PRefreshToken([Redacted])")
+        write!(f, "PRefreshToken([Redacted])")
+    }
+}
+
+#[derive(Clone)]
+pub struct PTokenSecret(pub String);
+
+impl Display for PTokenSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PTokenSecret([Redacted])")
+    }
+}
+
+impl Debug for PTokenSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PTokenSecret([Redacted])")
     }
 }
 
@@ -49,6 +83,16 @@ impl Default for RedditStrategy {
             token_uri: String::from(REDDIT_TOKEN_URL),
             request_uri: String::from(REDDIT_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::BasicAuth,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: true,
+            ok_envelope: false,
         }
     }
 }
@@ -63,6 +107,16 @@ impl Default for FacebookStrategy {
             token_uri: String::from(FACEBOOK_TOKEN_URL),
             request_uri: String::from(FACEBOOK_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
@@ -77,6 +131,16 @@ impl Default for GoogleStrategy {
             token_uri: String::from(GOOGLE_TOKEN_URL),
             request_uri: String::from(GOOGLE_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
@@ -91,6 +155,16 @@ impl Default for GithubStrategy {
             token_uri: String::from(GITHUB_TOKEN_URL),
             request_uri: String::from(GITHUB_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
@@ -105,6 +179,45 @@ impl Default for DiscordStrategy {
             token_uri: String::from(DISCORD_TOKEN_URL),
             request_uri: String::from(DISCORD_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
+        }
+    }
+}
+
+impl Default for LinkedinStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::from(LINKEDIN_AUTH_URL),
+            scopes: LINKEDIN_SCOPES
+                .iter()
+                .map(ToString::to_string)
+                .map(Scope::new)
+                .collect(),
+            token_uri: String::from(LINKEDIN_TOKEN_URL),
+            request_uri: String::from(LINKEDIN_REQUEST_URL),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            // Linkedin only accepts the client credentials in the request body.
+            auth_type: AuthType::RequestBody,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
@@ -119,6 +232,16 @@ impl Default for MicrosoftStrategy {
             token_uri: String::from(MICROSOFT_TOKEN_URL),
             request_uri: String::from(MICROSOFT_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
@@ -133,10 +256,267 @@ impl Default for FortyTwoStrategy {
             token_uri: String::from(FORTYTWO_TOKEN_URL),
             request_uri: String::from(FORTYTWO_REQUEST_URL),
             redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::Client,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: None,
+            token_profile: false,
+            ok_envelope: false,
         }
     }
 }
 
+/// How the `Authorization Code` and refresh tokens are exchanged at the token endpoint.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum TokenExchange {
+    /// Through an `oauth2` client configured like the [`BasicClient`], keeping the fields of the token response
+    /// beyond the standard ones, e.g. the `id_token`.
+    #[default]
+    Client,
+    /// A direct request carrying the client credentials in an HTTP Basic `Authorization` header and the
+    /// passport `User-Agent`, see [`basic_auth_token_request`]. Reddit and X (Twitter) need it.
+    BasicAuth,
+    /// A direct request carrying the client credentials in the request body, see [`request_body_token_request`].
+    /// Twitch needs it, since its token response doesn't follow the spec for the `scope`.
+    RequestBody,
+}
+
+/// The fields of the token response beyond the standard ones, e.g. the `id_token` of OpenID Connect, which the
+/// token response of the [`BasicClient`] leaves out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExtraFields {
+    #[serde(flatten)]
+    fields: HashMap<String, Value>,
+}
+
+impl ExtraTokenFields for ExtraFields {}
+
+/// A [`BasicClient`] keeping the [`ExtraFields`] of the token response.
+type TokenClient = oauth2::Client<
+    BasicErrorResponse,
+    StandardTokenResponse<ExtraFields, BasicTokenType>,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// The client of the [`TokenExchange::Client`] exchanges of the `strategy`, configured like its [`BasicClient`].
+fn token_client<S>(strategy: &S) -> Result<TokenClient, Error>
+where
+    S: Strategy + ?Sized,
+{
+    let client_secret = Some(strategy.client_secret())
+        .filter(|secret| !secret.is_empty())
+        .map(ClientSecret::new);
+
+    Ok(TokenClient::new(
+        ClientId::new(strategy.client_id()),
+        client_secret,
+        AuthUrl::new(strategy.auth_url())?,
+        Some(strategy.token_url()?),
+    )
+    .set_redirect_uri(RedirectUrl::new(strategy.redirect_url())?)
+    .set_auth_type(strategy.auth_type()))
+}
+
+/// The tokens issued by the provider token endpoint.
+#[derive(Debug, Clone)]
+pub struct TokenSet {
+    pub access_token: PAccessToken,
+    pub refresh_token: Option<PRefreshToken>,
+    /// The rest of the token response e.g. `expires_in`, `scope` or `id_token`.
+    pub extra: Value,
+}
+
+impl TryFrom<Value> for TokenSet {
+    type Error = Error;
+
+    fn try_from(mut value: Value) -> Result<Self, Self::Error> {
+        let map = value.as_object_mut().ok_or(Error::MissingAccessToken)?;
+
+        let access_token = match map.remove("access_token") {
+            Some(Value::String(token)) => PAccessToken(token),
+            _ => return Err(Error::MissingAccessToken),
+        };
+
+        let refresh_token = match map.remove("refresh_token") {
+            Some(Value::String(token)) => Some(PRefreshToken(token)),
+            _ => None,
+        };
+
+        Ok(Self {
+            access_token,
+            refresh_token,
+            extra: value,
+        })
+    }
+}
+
+/// Requests the token endpoint of the `strategy` with the client credentials in an HTTP Basic `Authorization`
+/// header, as required for confidential clients by providers like Reddit and X (Twitter).
+/// `params` holds the grant e.g. `[("grant_type", "refresh_token"), ("refresh_token", ..)]`.
+pub async fn basic_auth_token_request<S>(
+    strategy: &S,
+    params: &[(&str, &str)],
+) -> Result<TokenSet, Error>
+where
+    S: Strategy + ?Sized,
+{
+    direct_token_request(strategy, TokenExchange::BasicAuth, params).await
+}
+
+/// Requests the token endpoint of the `strategy` with the client credentials in the request body.
+/// `params` holds the grant, see [`basic_auth_token_request`].
+pub async fn request_body_token_request<S>(
+    strategy: &S,
+    params: &[(&str, &str)],
+) -> Result<TokenSet, Error>
+where
+    S: Strategy + ?Sized,
+{
+    direct_token_request(strategy, TokenExchange::RequestBody, params).await
+}
+
+async fn direct_token_request<S>(
+    strategy: &S,
+    exchange: TokenExchange,
+    params: &[(&str, &str)],
+) -> Result<TokenSet, Error>
+where
+    S: Strategy + ?Sized,
+{
+    let (client_id, client_secret) = (strategy.client_id(), strategy.client_secret());
+    let request = reqwest::Client::new()
+        .post(strategy.token_url()?.to_string())
+        .header(reqwest::header::USER_AGENT, Passport::USER_AGENT);
+
+    let request = if let TokenExchange::RequestBody = exchange {
+        let mut params = params.to_vec();
+        params.extend([
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+        ]);
+        request.form(&params)
+    } else {
+        request
+            .basic_auth(&client_id, Some(&client_secret))
+            .form(params)
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+    if response.status().is_success() {
+        response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))
+            .and_then(|value| {
+                if strategy.ok_envelope() {
+                    ok_or_error(value)
+                } else {
+                    Ok(value)
+                }
+            })
+            .and_then(TokenSet::try_from)
+    } else {
+        Err(Error::Reqwest(response.text().await.unwrap_or_default()))
+    }
+}
+
+/// Turns the `{"ok": false, "error": ..}` answer of the [`Strategy::ok_envelope`] providers into an error.
+fn ok_or_error(value: Value) -> Result<Value, Error> {
+    match value.get("ok").and_then(Value::as_bool) {
+        Some(false) => Err(Error::Reqwest(
+            value
+                .get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+                .to_string(),
+        )),
+        _ => Ok(value),
+    }
+}
+
+/// The claims of an `id_token`, e.g. the Microsoft `groups` and `roles` that are left out of the profile.
+/// The `id_token` is received directly from the token endpoint over TLS, so its signature is not checked
+/// ([OpenID Connect Core 3.1.3.7](https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation)).
+pub(crate) fn id_token_claims(id_token: &str) -> Option<Value> {
+    let payload = id_token.split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+
+    serde_json::from_slice(&payload).ok()
+}
+
+/// Requests the user profile at `request_uri` using the `access_token` as a bearer token,
+/// along with the extra `headers`.
+pub async fn request_profile(
+    request_uri: &str,
+    access_token: &PAccessToken,
+    headers: &[(String, String)],
+) -> Result<Value, Error> {
+    let mut request = reqwest::Client::new()
+        .get(request_uri)
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", access_token.0),
+        )
+        .header(reqwest::header::USER_AGENT, Passport::USER_AGENT);
+
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| Error::Reqwest(e.to_string()))?;
+
+    if response.status().is_success() {
+        response
+            .json::<Value>()
+            .await
+            .map_err(|error| Error::Reqwest(error.to_string()))
+    } else {
+        Err(Error::Reqwest(response.text().await.unwrap_or_default()))
+    }
+}
+
+/// The PKCE authorization url of [`Strategy::authorize`], with the `response_mode` and the
+/// [`Strategy::auth_params`] of the `strategy`.
+pub(crate) fn pkce_authorize<S>(strategy: &S, client: &BasicClient) -> (Url, CsrfToken, String)
+where
+    S: Strategy + ?Sized,
+{
+    let (pkce_challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+    let mut request = client
+        .authorize_url(CsrfToken::new_random)
+        .set_pkce_challenge(pkce_challenge)
+        .add_scopes(strategy.scopes());
+
+    if let ResponseMode::FormPost = strategy.response_mode() {
+        request = request.add_extra_param("response_mode", "form_post");
+    }
+
+    for (name, value) in strategy.auth_params() {
+        request = request.add_extra_param(name, value);
+    }
+
+    let (auth_url, csrf_token) = request.url();
+
+    (auth_url, csrf_token, verifier.secret().to_string())
+}
+
+#[async_trait]
 pub trait Strategy: Send + Sync {
     fn redirect_url(&self) -> String;
     fn request_uri(&self) -> String;
@@ -145,12 +525,226 @@ pub trait Strategy: Send + Sync {
     fn client_secret(&self) -> String;
     fn auth_url(&self) -> String;
     fn token_url(&self) -> Result<TokenUrl, Error>;
+
+    /// How the provider delivers the `code` and `state` to the redirect url.
+    fn response_mode(&self) -> ResponseMode {
+        ResponseMode::Query
+    }
+
+    /// How the `Authorization Code` and refresh tokens are exchanged at the token endpoint.
+    fn token_exchange(&self) -> TokenExchange {
+        TokenExchange::Client
+    }
+
+    /// Extra parameters added to the authorization url, e.g. the `team` Slack workspace.
+    fn auth_params(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// How the client credentials are sent to the token endpoint by the [`BasicClient`].
+    fn auth_type(&self) -> AuthType {
+        AuthType::BasicAuth
+    }
+
+    /// Builds the url the user is redirected to, along with its `state` and the secret kept until the provider
+    /// redirects back. By default, the url is built by the [`BasicClient`] and the secret is the PKCE verifier.
+    /// Strategies that need to talk to the provider first fail with [`Error::AsyncAuthorizationRequired`].
+    fn authorize(&self, client: &BasicClient) -> Result<(Url, CsrfToken, String), Error> {
+        Ok(pkce_authorize(self, client))
+    }
+
+    /// Builds the url the user is redirected to, for strategies that need to talk to the provider first,
+    /// e.g. to register the app on the user's Mastodon `instance`. Defaults to [`Strategy::authorize`].
+    async fn authorize_async(
+        &self,
+        client: &BasicClient,
+        _instance: Option<&str>,
+    ) -> Result<(Url, CsrfToken, String), Error> {
+        self.authorize(client)
+    }
+
+    /// Whether the redirect url is reached without the `state` from [`Strategy::authorize`], as with the Telegram
+    /// Login Widget. [`Strategy::callback`] is then called without any `state` check, and must authenticate
+    /// the data on its own.
+    fn stateless(&self) -> bool {
+        false
+    }
+
+    /// Completes the authentication once the `state` of the `statecode` is verified, with the `secret` from
+    /// [`Strategy::authorize`]. By default, the `Authorization Code` is exchanged with [`Strategy::exchange`].
+    async fn callback(
+        &self,
+        client: &BasicClient,
+        secret: String,
+        statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let code = statecode
+            .code
+            .clone()
+            .ok_or(Error::MissingAuthorizationCode)?;
+
+        self.exchange(
+            client,
+            AuthorizationCode::new(code),
+            PkceCodeVerifier::new(secret),
+            statecode,
+        )
+        .await
+    }
+
+    /// Exchanges the `Authorization Code` for the tokens and retrieves the user profile.
+    /// By default, the exchange follows [`Strategy::token_exchange`] and the profile is requested from
+    /// [`Strategy::request_uri`], unless it is the [`Strategy::token_profile`]. Providers that don't follow this model (e.g. Apple, which has no userinfo endpoint)
+    /// override it.
+    async fn exchange(
+        &self,
+        _client: &BasicClient,
+        code: AuthorizationCode,
+        verifier: PkceCodeVerifier,
+        _statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let tokens = match self.token_exchange() {
+            TokenExchange::Client => token_client(self)?
+                .exchange_code(code)
+                .set_pkce_verifier(verifier)
+                .request_async(async_http_client)
+                .await
+                .map_err(|err| Error::Reqwest(err.to_string()))
+                .and_then(|token| serde_json::to_value(token).map_err(Error::Json))
+                .and_then(TokenSet::try_from)?,
+            exchange => {
+                direct_token_request(
+                    self,
+                    exchange,
+                    &[
+                        ("grant_type", "authorization_code"),
+                        ("code", code.secret()),
+                        ("redirect_uri", &self.redirect_url()),
+                        ("code_verifier", verifier.secret()),
+                    ],
+                )
+                .await?
+            }
+        };
+
+        let mut profile = if self.token_profile() {
+            tokens.extra.clone()
+        } else {
+            self.profile(&tokens.access_token).await?
+        };
+        if let Some(map) = profile.as_object_mut() {
+            map.remove("access_token");
+            map.remove("refresh_token");
+
+            if let Some(claims) = tokens.extra["id_token"].as_str().and_then(id_token_claims) {
+                map.insert(String::from("id_token_claims"), claims);
+            }
+        }
+
+        Ok(Oauth2ServerResponse {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            token_secret: None,
+            profile,
+            roles: Vec::new(),
+        })
+    }
+
+    /// Exchanges the `refresh_token` for a new access token following [`Strategy::token_exchange`].
+    async fn refresh(
+        &self,
+        _client: &BasicClient,
+        refresh_token: &PRefreshToken,
+    ) -> Result<TokenSet, Error> {
+        match self.token_exchange() {
+            TokenExchange::Client => token_client(self)?
+                .exchange_refresh_token(&RefreshToken::new(refresh_token.0.clone()))
+                .request_async(async_http_client)
+                .await
+                .map_err(|err| Error::Reqwest(err.to_string()))
+                .and_then(|token| serde_json::to_value(token).map_err(Error::Json))
+                .and_then(TokenSet::try_from),
+            exchange => {
+                direct_token_request(
+                    self,
+                    exchange,
+                    &[
+                        ("grant_type", "refresh_token"),
+                        ("refresh_token", &refresh_token.0),
+                    ],
+                )
+                .await
+            }
+        }
+    }
+
+    /// Extra headers sent along with the profile request, e.g. the `Client-Id` header required by Twitch.
+    fn profile_headers(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// A JSON pointer to the profile within the profile response, for providers that wrap it in an envelope,
+    /// e.g. `/data` for X (Twitter) or `/data/0` for Twitch.
+    fn profile_envelope(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the profile is the rest of the token response (e.g. `scope` and `expires_in`) instead of being
+    /// requested from [`Strategy::request_uri`]. Reddit does so, since its `/api/v1/me` needs the `identity` scope.
+    fn token_profile(&self) -> bool {
+        false
+    }
+
+    /// Whether the provider answers failures with `200 OK` and `{"ok": false, "error": ..}`, as Slack does. The
+    /// token and profile responses are then checked for it.
+    fn ok_envelope(&self) -> bool {
+        false
+    }
+
+    /// The enrichers run by [`Strategy::enrich`].
+    fn enrichers(&self) -> Vec<Arc<dyn Enricher>> {
+        Vec::new()
+    }
+
+    /// Makes extra calls with the `access_token` once the profile is retrieved, and merges their results into
+    /// the `profile`, e.g. the emails of a Github user. By default, the [`Strategy::enrichers`] are run in order.
+    async fn enrich(
+        &self,
+        http: &reqwest::Client,
+        access_token: &PAccessToken,
+        profile: &mut Value,
+    ) -> Result<(), Error> {
+        for enricher in self.enrichers() {
+            enricher.enrich(http, access_token, profile).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Requests the user profile from [`Strategy::request_uri`] using the `access_token` as a bearer token,
+    /// with the [`Strategy::profile_headers`], and unwraps it from the [`Strategy::profile_envelope`].
+    async fn profile(&self, access_token: &PAccessToken) -> Result<Value, Error> {
+        let mut profile =
+            request_profile(&self.request_uri(), access_token, &self.profile_headers()).await?;
+        if self.ok_envelope() {
+            profile = ok_or_error(profile)?;
+        }
+
+        match self.profile_envelope() {
+            Some(pointer) => profile
+                .pointer_mut(&pointer)
+                .map(Value::take)
+                .ok_or(Error::MissingProfile),
+            None => Ok(profile),
+        }
+    }
 }
 
 macro_rules! create_struct {
-    ($($name:ident),*) => {
+    ($($(#[$meta:meta])* $name:ident),*) => {
         use crate::error::Error;
         $(
+            $(#[$meta])*
             #[derive(Clone)]
             pub struct $name {
                 pub(crate) client_id: String,
@@ -160,6 +754,23 @@ macro_rules! create_struct {
                 pub(crate) request_uri: String,
                 pub(crate) token_uri: String,
                 pub(crate) redirect_uri: String,
+                pub(crate) response_mode: ResponseMode,
+                pub(crate) token_exchange: TokenExchange,
+                pub(crate) auth_type: AuthType,
+                pub(crate) enrichers: Vec<Arc<dyn Enricher>>,
+                /// See [`Strategy::auth_params`].
+                pub(crate) auth_params: Vec<(String, String)>,
+                /// A header carrying the client id along with the profile request, e.g. Twitch's `Client-Id`.
+                pub(crate) client_id_header: Option<String>,
+                /// The profile is requested from the userinfo endpoint instead when only the OpenID Connect scopes
+                /// are requested.
+                pub(crate) userinfo_uri: Option<String>,
+                /// See [`Strategy::profile_envelope`].
+                pub(crate) profile_envelope: Option<String>,
+                /// See [`Strategy::token_profile`].
+                pub(crate) token_profile: bool,
+                /// See [`Strategy::ok_envelope`].
+                pub(crate) ok_envelope: bool,
             }
 
             impl $name {
@@ -174,16 +785,54 @@ macro_rules! create_struct {
                     strategy.client_secret.push_str(client_secret);
                     strategy.redirect_uri.push_str(redirect_uri);
 
-                    strategy
-                        .scopes
-                        .extend(scopes.iter().map(ToString::to_string).map(Scope::new));
+                    // The given scopes replace the default ones of the strategy, if any.
+                    if !scopes.is_empty() {
+                        strategy.scopes =
+                            scopes.iter().map(ToString::to_string).map(Scope::new).collect();
+                    }
                     strategy
                 }
+
+                /// Sets how the provider delivers the `code` and `state` to the redirect url,
+                /// e.g. [`ResponseMode::FormPost`] for a Microsoft app configured for it.
+                pub fn set_response_mode(mut self, response_mode: ResponseMode) -> Self {
+                    self.response_mode = response_mode;
+                    self
+                }
+
+                /// Adds an enricher run once the profile is retrieved, e.g. [`super::EndpointEnricher::github_emails`].
+                pub fn add_enricher<E>(mut self, enricher: E) -> Self
+                where
+                    E: Enricher + 'static,
+                {
+                    self.enrichers.push(Arc::new(enricher));
+                    self
+                }
+
+                /// Removes the enrichers known by `key`, e.g. `accessible_resources` for the one Atlassian runs
+                /// by default.
+                pub fn remove_enricher(mut self, key: &str) -> Self {
+                    self.enrichers.retain(|enricher| enricher.key() != Some(key));
+                    self
+                }
+
+                /// Removes all the enrichers, including the ones the strategy runs by default.
+                pub fn clear_enrichers(mut self) -> Self {
+                    self.enrichers.clear();
+                    self
+                }
             }
 
             impl Strategy for $name {
                 fn request_uri(&self) -> String {
-                    self.request_uri.clone()
+                    let oidc = ["openid", "profile", "email"];
+                    let only_oidc = !self.scopes.is_empty()
+                        && self.scopes.iter().all(|scope| oidc.contains(&scope.as_str()));
+
+                    match &self.userinfo_uri {
+                        Some(userinfo_uri) if only_oidc => userinfo_uri.clone(),
+                        _ => self.request_uri.clone(),
+                    }
                 }
                 fn scopes(&self) -> Vec<Scope> {
                     self.scopes.clone()
@@ -204,6 +853,45 @@ macro_rules! create_struct {
                     self.redirect_uri.clone()
                 }
 
+                fn response_mode(&self) -> ResponseMode {
+                    self.response_mode
+                }
+
+                fn token_exchange(&self) -> TokenExchange {
+                    self.token_exchange
+                }
+
+                fn auth_type(&self) -> AuthType {
+                    self.auth_type.clone()
+                }
+
+                fn auth_params(&self) -> Vec<(String, String)> {
+                    self.auth_params.clone()
+                }
+
+                fn enrichers(&self) -> Vec<Arc<dyn Enricher>> {
+                    self.enrichers.clone()
+                }
+
+                fn profile_headers(&self) -> Vec<(String, String)> {
+                    self.client_id_header
+                        .iter()
+                        .map(|header| (header.clone(), self.client_id.clone()))
+                        .collect()
+                }
+
+                fn profile_envelope(&self) -> Option<String> {
+                    self.profile_envelope.clone()
+                }
+
+                fn token_profile(&self) -> bool {
+                    self.token_profile
+                }
+
+                fn ok_envelope(&self) -> bool {
+                    self.ok_envelope
+                }
+
                 fn token_url(&self) -> Result<TokenUrl, Error> {
                     match TokenUrl::new(self.token_uri.clone()) {
                         Ok(token) => Ok(token),
@@ -224,5 +912,148 @@ create_struct!(
     FacebookStrategy,
     DiscordStrategy,
     FortyTwoStrategy,
-    RedditStrategy
+    RedditStrategy,
+    LinkedinStrategy,
+    /// X (Twitter) OAuth 2.0 with PKCE.
+    ///
+    /// The token endpoint requires the client credentials as HTTP Basic auth for confidential clients.
+    /// Add the `offline.access` scope to receive a refresh token, e.g. `&["tweet.read", "users.read", "offline.access"]`.
+    /// The profile is returned without the `data` envelope of `/2/users/me`.
+    TwitterStrategy,
+    /// Gitlab, either gitlab.com or a self-managed instance set with [`GitlabStrategy::set_base_url`].
+    ///
+    /// The profile is requested from `/api/v4/user`, which needs the `read_user` (or `read_api`/`api`) scope.
+    /// When only the OpenID Connect scopes (`openid`, `profile`, `email`) are requested, the profile is requested
+    /// from `/oauth/userinfo` instead.
+    GitlabStrategy,
+    /// Twitch.
+    ///
+    /// The Helix API requires the `Client-Id` header along with the access token, and returns the user
+    /// in a `data` array. The profile is returned without that envelope.
+    /// Request the `user:read:email` scope to receive the user's email.
+    TwitchStrategy,
+    /// Sign in with Slack (OpenID Connect).
+    ///
+    /// The `openid`, `profile` and `email` scopes are requested when no scopes are given. Use
+    /// [`SlackStrategy::set_team`] to pin the login to a workspace; the `https://slack.com/team_id` claim of the
+    /// profile is then verified, failing with [`Error::SlackTeamMismatch`].
+    SlackStrategy,
+    /// Atlassian (OAuth 2.0 3LO), for Jira and Confluence Cloud.
+    ///
+    /// The `read:me` and `offline_access` scopes are requested when no scopes are given. The profile is the user
+    /// from `/me`, along with the cloud sites the user granted access to from
+    /// [`EndpointEnricher::atlassian_resources`](super::EndpointEnricher::atlassian_resources).
+    /// Atlassian rotates refresh tokens: each [`Passport::refresh`](crate::passport::Passport::refresh) returns a
+    /// new refresh token that replaces the one used.
+    AtlassianStrategy
 );
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use oauth2::basic::BasicClient;
+    use serde_json::json;
+
+    use super::*;
+    use crate::roles::RoleMapping;
+    use crate::testing::serve;
+
+    /// An unsigned `id_token` carrying the `claims`.
+    fn id_token(claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{payload}.")
+    }
+
+    fn statecode() -> StateCode {
+        StateCode {
+            state: None,
+            code: Some(String::from("code")),
+            user: None,
+            params: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_id_token_claims() {
+        let claims = json!({ "tid": "ourco", "groups": ["8f1c3b7e-admins"] });
+        let token = json!({
+            "access_token": "access",
+            "token_type": "Bearer",
+            "refresh_token": "refresh",
+            "id_token": id_token(claims.clone()),
+        });
+        let (url, requests) = serve(vec![
+            ("/token", 200, token),
+            ("/me", 200, json!({ "id": "ada" })),
+        ])
+        .await;
+
+        let mut strategy =
+            MicrosoftStrategy::new("id", "secret", &["openid"], "https://ourco.com/callback");
+        strategy.token_uri = format!("{url}/token");
+        strategy.request_uri = format!("{url}/me");
+        let client = BasicClient::new(
+            ClientId::new(strategy.client_id()),
+            None,
+            AuthUrl::new(strategy.auth_url()).unwrap(),
+            None,
+        );
+
+        let response = strategy
+            .exchange(
+                &client,
+                AuthorizationCode::new(String::from("code")),
+                PkceCodeVerifier::new(String::from("verifier")),
+                &statecode(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.access_token.0, "access");
+        assert_eq!(response.refresh_token.unwrap().0, "refresh");
+        assert_eq!(response.profile["id"], "ada");
+        assert_eq!(response.profile["id_token_claims"], claims);
+
+        let mapping =
+            RoleMapping::default().map("/id_token_claims/groups", "8f1c3b7e-admins", "admin");
+        assert_eq!(mapping.roles(&response.profile), vec!["admin"]);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("POST /token "));
+        assert!(requests[0].contains("code_verifier=verifier"));
+        assert!(requests[1]
+            .to_ascii_lowercase()
+            .contains("authorization: bearer access"));
+    }
+
+    #[tokio::test]
+    async fn refreshes_with_the_extra_fields() {
+        let token = json!({
+            "access_token": "access",
+            "token_type": "Bearer",
+            "expires_in": 3600,
+            "id_token": "header.payload.",
+        });
+        let (url, _) = serve(vec![("/token", 200, token)]).await;
+
+        let mut strategy =
+            GoogleStrategy::new("id", "secret", &["openid"], "https://ourco.com/callback");
+        strategy.token_uri = format!("{url}/token");
+        let client = BasicClient::new(
+            ClientId::new(strategy.client_id()),
+            None,
+            AuthUrl::new(strategy.auth_url()).unwrap(),
+            None,
+        );
+
+        let tokens = strategy
+            .refresh(&client, &PRefreshToken(String::from("refresh")))
+            .await
+            .unwrap();
+        assert_eq!(tokens.access_token.0, "access");
+        assert_eq!(tokens.extra["id_token"], "header.payload.");
+        assert_eq!(tokens.extra["expires_in"], 3600);
+    }
+}
@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use oauth2::basic::BasicClient;
+use oauth2::url::Url;
+use oauth2::{CsrfToken, Scope, TokenUrl};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::{PAccessToken, Strategy, TELEGRAM_AUTH_URL, TELEGRAM_MAX_AGE};
+use crate::error::Error;
+use crate::passport::{now, Oauth2ServerResponse, StateCode};
+
+/// Telegram Login Widget.
+///
+/// Telegram doesn't redirect through an OAuth server: render the widget with its `data-auth-url` set to the
+/// redirect url (which [`Passport::redirect_url`](crate::passport::Passport::redirect_url) returns for this
+/// strategy), and pass the `id`, `first_name`, `username`, `photo_url`, `auth_date` and `hash` it sends there to
+/// [`Passport::authenticate`](crate::passport::Passport::authenticate).
+/// The data is authenticated by its `hash`, an HMAC-SHA256 keyed by the SHA256 of the bot token, and rejected once
+/// its `auth_date` is older than the max age (a day by default). There are no tokens, so the `access_token`
+/// of the response is empty.
+#[derive(Clone)]
+pub struct TelegramStrategy {
+    pub(crate) bot_token: String,
+    pub(crate) redirect_uri: String,
+    pub(crate) max_age: u64,
+}
+
+impl TelegramStrategy {
+    pub fn new(bot_token: &str, redirect_uri: &str) -> Self {
+        Self {
+            bot_token: bot_token.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            max_age: TELEGRAM_MAX_AGE,
+        }
+    }
+
+    /// Sets how old, in seconds, the `auth_date` of the login data may be.
+    pub fn set_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Verifies the `hash` and the `auth_date` of the login data and returns it without the `hash`.
+    fn verify(&self, params: &[(&String, &String)]) -> Result<Map<String, Value>, Error> {
+        let hash = params
+            .iter()
+            .find(|(name, _)| name.as_str() == "hash")
+            .and_then(|(_, hash)| hex::decode(hash).ok())
+            .ok_or_else(|| Error::InvalidTelegramData(String::from("missing hash")))?;
+
+        let mut data = params
+            .iter()
+            .filter(|(name, _)| name.as_str() != "hash")
+            .collect::<Vec<_>>();
+        data.sort();
+
+        let data_check_string = data
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&Sha256::digest(self.bot_token.as_bytes()))
+            .map_err(|error| Error::InvalidTelegramData(error.to_string()))?;
+        mac.update(data_check_string.as_bytes());
+        mac.verify_slice(&hash)
+            .map_err(|_| Error::InvalidTelegramData(String::from("hash mismatch")))?;
+
+        let auth_date = data
+            .iter()
+            .find(|(name, _)| name.as_str() == "auth_date")
+            .and_then(|(_, date)| date.parse::<u64>().ok())
+            .ok_or_else(|| Error::InvalidTelegramData(String::from("missing auth_date")))?;
+        if now().saturating_sub(auth_date) > self.max_age {
+            return Err(Error::InvalidTelegramData(String::from("stale auth_date")));
+        }
+
+        Ok(data
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::String(value.to_string())))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Strategy for TelegramStrategy {
+    fn request_uri(&self) -> String {
+        String::new()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        Vec::new()
+    }
+
+    fn client_id(&self) -> String {
+        String::new()
+    }
+
+    fn client_secret(&self) -> String {
+        String::new()
+    }
+
+    fn auth_url(&self) -> String {
+        String::from(TELEGRAM_AUTH_URL)
+    }
+
+    fn redirect_url(&self) -> String {
+        self.redirect_uri.clone()
+    }
+
+    fn token_url(&self) -> Result<TokenUrl, Error> {
+        match TokenUrl::new(String::from(TELEGRAM_AUTH_URL)) {
+            Ok(token) => Ok(token),
+            Err(err) => Err(Error::ParseError(err)),
+        }
+    }
+
+    fn stateless(&self) -> bool {
+        true
+    }
+
+    /// The widget's `data-auth-url`, i.e. the redirect url.
+    fn authorize(&self, _client: &BasicClient) -> Result<(Url, CsrfToken, String), Error> {
+        Ok((
+            Url::parse(&self.redirect_uri)?,
+            CsrfToken::new(String::new()),
+            String::new(),
+        ))
+    }
+
+    async fn callback(
+        &self,
+        _client: &BasicClient,
+        _secret: String,
+        statecode: &StateCode,
+    ) -> Result<Oauth2ServerResponse, Error> {
+        let params = statecode.params.iter().collect::<Vec<_>>();
+        let profile = self.verify(&params)?;
+
+        Ok(Oauth2ServerResponse {
+            access_token: PAccessToken(String::new()),
+            refresh_token: None,
+            token_secret: None,
+            profile: Value::Object(profile),
+            roles: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT_TOKEN: &str = "123456:ABC-DEF1234ghIkl-zyx57W2v1u123ew11";
+
+    /// Login data signed with [`BOT_TOKEN`], as the widget sends it.
+    fn login_data() -> Vec<(String, String)> {
+        [
+            ("id", "42"),
+            ("first_name", "Ada"),
+            ("username", "ada"),
+            ("auth_date", "1700000000"),
+            (
+                "hash",
+                "6c3477126e81bb583e07b1c12d0bd4accb19d6c59b185e3aea50ed68ca124c6f",
+            ),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+    }
+
+    fn verify(
+        strategy: &TelegramStrategy,
+        data: &[(String, String)],
+    ) -> Result<Map<String, Value>, Error> {
+        let params = data
+            .iter()
+            .map(|(name, value)| (name, value))
+            .collect::<Vec<_>>();
+        strategy.verify(&params)
+    }
+
+    #[test]
+    fn accepts_signed_data() {
+        let strategy =
+            TelegramStrategy::new(BOT_TOKEN, "https://example.com/callback").set_max_age(u64::MAX);
+
+        let profile = verify(&strategy, &login_data()).unwrap();
+
+        assert_eq!(profile["id"], "42");
+        assert_eq!(profile["username"], "ada");
+        assert!(!profile.contains_key("hash"));
+    }
+
+    #[test]
+    fn rejects_tampered_data() {
+        let strategy =
+            TelegramStrategy::new(BOT_TOKEN, "https://example.com/callback").set_max_age(u64::MAX);
+        let mut data = login_data();
+        data[0].1 = String::from("43");
+
+        let error = verify(&strategy, &data).unwrap_err();
+
+        assert!(matches!(error, Error::InvalidTelegramData(reason) if reason == "hash mismatch"));
+    }
+
+    #[test]
+    fn rejects_expired_data() {
+        let strategy = TelegramStrategy::new(BOT_TOKEN, "https://example.com/callback");
+
+        let error = verify(&strategy, &login_data()).unwrap_err();
+
+        assert!(matches!(error, Error::InvalidTelegramData(reason) if reason == "stale auth_date"));
+    }
+}
@@ -0,0 +1,28 @@
+use oauth2::AuthType;
+
+use super::{TokenExchange, TwitchStrategy, TWITCH_AUTH_URL, TWITCH_REQUEST_URL, TWITCH_TOKEN_URL};
+use crate::passport::ResponseMode;
+
+impl Default for TwitchStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::from(TWITCH_AUTH_URL),
+            scopes: Vec::new(),
+            token_uri: String::from(TWITCH_TOKEN_URL),
+            request_uri: String::from(TWITCH_REQUEST_URL),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::RequestBody,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            profile_envelope: Some(String::from("/data/0")),
+            token_profile: false,
+            ok_envelope: false,
+            userinfo_uri: None,
+            client_id_header: Some(String::from("Client-Id")),
+        }
+    }
+}
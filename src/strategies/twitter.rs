@@ -0,0 +1,30 @@
+use oauth2::AuthType;
+
+use super::{
+    TokenExchange, TwitterStrategy, TWITTER_AUTH_URL, TWITTER_REQUEST_URL, TWITTER_TOKEN_URL,
+};
+use crate::passport::ResponseMode;
+
+impl Default for TwitterStrategy {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            auth_uri: String::from(TWITTER_AUTH_URL),
+            scopes: Vec::new(),
+            token_uri: String::from(TWITTER_TOKEN_URL),
+            request_uri: String::from(TWITTER_REQUEST_URL),
+            redirect_uri: String::new(),
+            response_mode: ResponseMode::Query,
+            token_exchange: TokenExchange::BasicAuth,
+            auth_type: AuthType::BasicAuth,
+            enrichers: Vec::new(),
+            auth_params: Vec::new(),
+            client_id_header: None,
+            userinfo_uri: None,
+            profile_envelope: Some(String::from("/data")),
+            token_profile: false,
+            ok_envelope: false,
+        }
+    }
+}
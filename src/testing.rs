@@ -0,0 +1,65 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// The requests received by a [`serve`]d provider, each as its request line, headers and body.
+pub(crate) type Requests = Arc<Mutex<Vec<String>>>;
+
+/// Serves the `routes` of a fake provider on a local port, answering each request with the status and JSON body
+/// of the first route its path starts with, or `404 Not Found`. Returns the base url of the server.
+pub(crate) async fn serve(routes: Vec<(&'static str, u16, Value)>) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let requests = Requests::default();
+
+    let received = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let request = read(&mut stream).await;
+            let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+            received.lock().unwrap().push(request);
+
+            let (status, body) = routes
+                .iter()
+                .find(|(prefix, _, _)| path.starts_with(prefix))
+                .map(|(_, status, body)| (*status, body.to_string()))
+                .unwrap_or((404, String::new()));
+            let response = format!(
+                "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (format!("http://{address}"), requests)
+}
+
+/// Reads a whole request, up to the `Content-Length` of its body.
+async fn read(stream: &mut tokio::net::TcpStream) -> String {
+    let mut request = Vec::new();
+    let mut buffer = [0; 4096];
+    while let Ok(read) = stream.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+
+        let text = String::from_utf8_lossy(&request);
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= length {
+                break;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&request).into_owned()
+}